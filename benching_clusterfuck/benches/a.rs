@@ -13,7 +13,7 @@ fn setup_lcjc() -> LCJCSkipfield {
     let mut sf = LCJCSkipfield::new(N);
     let mut rng = StdRng::seed_from_u64(42);
     for i in 0..N {
-        if rng.gen::<f64>() < SKIP_RATIO {
+        if rng.random::<f64>() < SKIP_RATIO {
             sf.skip(i);
         }
     }
@@ -24,7 +24,7 @@ fn setup_bitmask() -> BitmaskSkipfield {
     let mut sf = BitmaskSkipfield::new(N);
     let mut rng = StdRng::seed_from_u64(42);
     for i in 0..N {
-        if rng.gen::<f64>() < SKIP_RATIO {
+        if rng.random::<f64>() < SKIP_RATIO {
             sf.skip(i);
         }
     }
@@ -71,7 +71,7 @@ criterion_main!(iter_benches);
 //     let mut rng = StdRng::seed_from_u64(42);
 
 //     for i in 0..N {
-//         if rng.gen::<f64>() < SKIP_RATIO {
+//         if rng.random::<f64>() < SKIP_RATIO {
 //             sf.skip(i);
 //         }
 //     }
@@ -84,7 +84,7 @@ criterion_main!(iter_benches);
 //     let mut rng = StdRng::seed_from_u64(42);
 
 //     for i in 0..N {
-//         if rng.gen::<f64>() < SKIP_RATIO {
+//         if rng.random::<f64>() < SKIP_RATIO {
 //             sf.skip(i);
 //         }
 //     }
@@ -97,7 +97,7 @@ criterion_main!(iter_benches);
 //     let mut rng = StdRng::seed_from_u64(42);
 
 //     for i in 0..N {
-//         if rng.gen::<f64>() < SKIP_RATIO {
+//         if rng.random::<f64>() < SKIP_RATIO {
 //             sf.skip(i);
 //         }
 //     }
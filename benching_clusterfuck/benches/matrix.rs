@@ -0,0 +1,408 @@
+//! Every skipfield against every operation, across skip densities, skip
+//! patterns and field sizes.
+//!
+//! Benchmarks are named `{size}/{pattern}/{density}%/{impl}/{op}`, so a slice
+//! of the matrix can be picked with criterion's filter, e.g.
+//!
+//!     cargo bench --bench matrix --features lockless,optimized_seq -- '1000000/clustered/.*/(bitmask|lcjc_u32)/'
+//!
+//! Fields are only built for benchmarks that pass the filter. Sizes default
+//! to 1K through 100M; set `SKIPFIELD_BENCH_SIZES=1000,1000000` to pick others.
+
+use std::time::{Duration, Instant};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkGroup, Criterion};
+use criterion::measurement::WallTime;
+use benching_clusterfuck::{
+    bitmask_skipfield::BitmaskSkipfield,
+    bool_skipfield::BoolSkipfield,
+    compressed_skipfield::CompressedSkipfield,
+    hcjc_skipfield::HCJCSkipfield,
+    lcjc_skipfield::LCJCSkipfield,
+};
+use lockless_boolean_skipfield::{LockLessBitmaskSkipfield, LockLessBoolSkipfield};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use skipfield_core::Skipfield;
+
+const SIZES: [usize; 4] = [1_000, 100_000, 10_000_000, 100_000_000];
+const DENSITIES: [usize; 8] = [0, 1, 10, 30, 50, 70, 90, 99];
+// indices each skip/unskip sample cycles through
+const SAMPLE_INDICES: usize = 1024;
+// mean length of a skipped run in the clustered pattern
+const CLUSTER_LEN: usize = 64;
+
+#[derive(Clone, Copy)]
+enum Pattern {
+    // every slot skipped independently
+    Uniform,
+    // skipped slots come in runs of around `CLUSTER_LEN`
+    Clustered,
+    // skipped slots spread evenly, so 50% alternates slot by slot
+    Alternating,
+    // one skipped run at the end of the field
+    AllAtEnd,
+}
+
+impl Pattern {
+    const ALL: [Pattern; 4] = [Pattern::Uniform, Pattern::Clustered, Pattern::Alternating, Pattern::AllAtEnd];
+
+    fn name(self) -> &'static str {
+        match self {
+            Pattern::Uniform => "uniform",
+            Pattern::Clustered => "clustered",
+            Pattern::Alternating => "alternating",
+            Pattern::AllAtEnd => "all_at_end",
+        }
+    }
+}
+
+struct Scenario {
+    len: usize,
+    pattern: Pattern,
+    density: usize,
+}
+
+impl Scenario {
+    // the field as a bitmask, which every representation is converted from
+    fn build(&self) -> BitmaskSkipfield {
+        let (len, density) = (self.len, self.density);
+        let mut words = vec![0u64; len.div_ceil(64)];
+        let mut rng = StdRng::seed_from_u64(42);
+        match self.pattern {
+            Pattern::Uniform => {
+                let p = density as f64 / 100.0;
+                for i in 0..len {
+                    if rng.random::<f64>() < p {
+                        words[i / 64] |= 1 << (i % 64);
+                    }
+                }
+            }
+            Pattern::Clustered if density > 0 => {
+                // alternate active gaps and skipped runs with means in the
+                // ratio of the density. the mean gap is under one slot at 99%,
+                // so it's drawn in floating point and rounded per gap
+                let gap_mean = (CLUSTER_LEN * (100 - density)) as f64 / density as f64;
+                let gap = |rng: &mut StdRng| (rng.random::<f64>() * 2.0 * gap_mean).round() as usize;
+                // the first run lands inside the field even when the mean
+                // gap is longer than it
+                let mut pos = gap(&mut rng).min(len.saturating_sub(1));
+                while pos < len {
+                    let run = rng.random_range(1..2 * CLUSTER_LEN);
+                    set_bits(&mut words, pos..(pos + run).min(len));
+                    pos += run + gap(&mut rng);
+                }
+            }
+            Pattern::Clustered => {}
+            Pattern::Alternating => {
+                for i in 0..len {
+                    if (i + 1) * density / 100 > i * density / 100 {
+                        words[i / 64] |= 1 << (i % 64);
+                    }
+                }
+            }
+            Pattern::AllAtEnd => set_bits(&mut words, len - len * density / 100..len),
+        }
+        let sf = BitmaskSkipfield::from_words(words, len);
+
+        // the skip benchmark needs active slots, and small fields hold too
+        // few runs to land near the target
+        assert!(sf.count_active() > 0, "{} at {density}% has no active slots", self.pattern.name());
+        if len >= 100_000 {
+            let achieved = 100.0 * sf.count_skipped() as f64 / len as f64;
+            assert!(
+                (achieved - density as f64).abs() < 2.0,
+                "{} at {density}% came out {achieved:.1}% skipped",
+                self.pattern.name()
+            );
+        }
+        sf
+    }
+}
+
+fn set_bits(words: &mut [u64], range: std::ops::Range<usize>) {
+    for i in range {
+        words[i / 64] |= 1 << (i % 64);
+    }
+}
+
+/// A representation under test, built from the scenario's bitmask.
+trait Subject: Skipfield {
+    const NAME: &'static str;
+
+    fn from_source(source: &BitmaskSkipfield) -> Self;
+}
+
+fn from_runs<T: Skipfield>(mut sf: T, source: &BitmaskSkipfield) -> T {
+    for (start, len) in source.skipped_runs() {
+        sf.skip_range(start..start + len);
+    }
+    sf
+}
+
+impl Subject for BoolSkipfield {
+    const NAME: &'static str = "bool";
+
+    fn from_source(source: &BitmaskSkipfield) -> Self {
+        BoolSkipfield::from(source)
+    }
+}
+
+impl Subject for boolean_skipfield::BoolSkipfield {
+    const NAME: &'static str = "bool_crate";
+
+    fn from_source(source: &BitmaskSkipfield) -> Self {
+        from_runs(Self::new(Skipfield::len(source)), source)
+    }
+}
+
+impl Subject for BitmaskSkipfield {
+    const NAME: &'static str = "bitmask";
+
+    fn from_source(source: &BitmaskSkipfield) -> Self {
+        source.clone()
+    }
+}
+
+// a plain u8 field can't hold runs over 255 slots, so chain them
+struct ChainedLcjc(LCJCSkipfield);
+
+impl Skipfield for ChainedLcjc {
+    fn new(len: usize) -> Self {
+        ChainedLcjc(LCJCSkipfield::new_chained(len))
+    }
+
+    fn len(&self) -> usize {
+        Skipfield::len(&self.0)
+    }
+
+    fn skip(&mut self, idx: usize) {
+        self.0.skip(idx)
+    }
+
+    fn unskip(&mut self, idx: usize) {
+        self.0.unskip(idx)
+    }
+
+    fn is_skipped(&self, idx: usize) -> bool {
+        self.0.is_skipped(idx)
+    }
+
+    fn skip_range(&mut self, range: std::ops::Range<usize>) {
+        self.0.skip_range(range)
+    }
+
+    fn first_active(&self) -> Option<usize> {
+        self.0.first_active()
+    }
+
+    fn count_skipped(&self) -> usize {
+        self.0.count_skipped()
+    }
+
+    fn active_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.active_indices()
+    }
+}
+
+impl Subject for ChainedLcjc {
+    const NAME: &'static str = "lcjc_u8_chained";
+
+    fn from_source(source: &BitmaskSkipfield) -> Self {
+        from_runs(ChainedLcjc::new(Skipfield::len(source)), source)
+    }
+}
+
+impl Subject for LCJCSkipfield<u32> {
+    const NAME: &'static str = "lcjc_u32";
+
+    fn from_source(source: &BitmaskSkipfield) -> Self {
+        LCJCSkipfield::try_from(source).expect("runs fit u32 nodes")
+    }
+}
+
+impl Subject for HCJCSkipfield<u32> {
+    const NAME: &'static str = "hcjc_u32";
+
+    fn from_source(source: &BitmaskSkipfield) -> Self {
+        from_runs(HCJCSkipfield::with_len(Skipfield::len(source)), source)
+    }
+}
+
+impl Subject for CompressedSkipfield {
+    const NAME: &'static str = "compressed";
+
+    fn from_source(source: &BitmaskSkipfield) -> Self {
+        let mut sf = from_runs(CompressedSkipfield::new(Skipfield::len(source)), source);
+        sf.optimize();
+        sf
+    }
+}
+
+impl Subject for LockLessBoolSkipfield {
+    const NAME: &'static str = "lockless_bool";
+
+    fn from_source(source: &BitmaskSkipfield) -> Self {
+        LockLessBoolSkipfield::from(source)
+    }
+}
+
+impl Subject for LockLessBitmaskSkipfield {
+    const NAME: &'static str = "lockless_bitmask";
+
+    fn from_source(source: &BitmaskSkipfield) -> Self {
+        LockLessBitmaskSkipfield::from(source)
+    }
+}
+
+impl Subject for optimized_seq_skipfield::Skipfield {
+    const NAME: &'static str = "optimized_seq";
+
+    fn from_source(source: &BitmaskSkipfield) -> Self {
+        Self::from(source)
+    }
+}
+
+// state shared by every representation of one scenario, built on first use
+struct Shared<'a> {
+    scenario: &'a Scenario,
+    source: Option<BitmaskSkipfield>,
+    // random active and skipped indices for the skip and unskip benchmarks
+    samples: Option<(Vec<usize>, Vec<usize>)>,
+}
+
+impl Shared<'_> {
+    fn source(&mut self) -> &BitmaskSkipfield {
+        self.source.get_or_insert_with(|| self.scenario.build())
+    }
+
+    fn samples(&mut self) -> &(Vec<usize>, Vec<usize>) {
+        if self.samples.is_none() {
+            let source = self.source();
+            let samples = (sample_active(source), sample_active(&source.complement()));
+            self.samples = Some(samples);
+        }
+        self.samples.as_ref().unwrap()
+    }
+}
+
+fn sample_active(sf: &BitmaskSkipfield) -> Vec<usize> {
+    let count = sf.count_active();
+    if count == 0 {
+        return Vec::new();
+    }
+    let mut sf = sf.clone();
+    sf.build_rank_directory();
+    let mut rng = StdRng::seed_from_u64(7);
+    (0..SAMPLE_INDICES).map(|_| sf.select(rng.random_range(0..count)).unwrap()).collect()
+}
+
+fn bench_subject<T: Subject>(group: &mut BenchmarkGroup<'_, WallTime>, shared: &mut Shared<'_>) {
+    // built on first use, so filtered-out benchmarks cost nothing
+    let mut field: Option<T> = None;
+    let name = T::NAME;
+
+    group.bench_function(format!("{name}/first_active"), |b| {
+        let sf = field.get_or_insert_with(|| T::from_source(shared.source()));
+        b.iter(|| black_box(sf.first_active()));
+    });
+    group.bench_function(format!("{name}/count_skipped"), |b| {
+        let sf = field.get_or_insert_with(|| T::from_source(shared.source()));
+        b.iter(|| black_box(sf.count_skipped()));
+    });
+    group.bench_function(format!("{name}/count_active"), |b| {
+        let sf = field.get_or_insert_with(|| T::from_source(shared.source()));
+        b.iter(|| black_box(sf.count_active()));
+    });
+    group.bench_function(format!("{name}/iter_active"), |b| {
+        let sf = field.get_or_insert_with(|| T::from_source(shared.source()));
+        b.iter(|| black_box(sf.active_indices().fold(0usize, |acc, i| acc.wrapping_add(i))));
+    });
+
+    // `build` makes sure every field has some active slots, but 0% has
+    // nothing to unskip
+    group.bench_function(format!("{name}/skip"), |b| {
+        let sf = field.get_or_insert_with(|| T::from_source(shared.source()));
+        let indices = &shared.samples().0;
+        toggle(b, sf, indices, |sf, i| sf.skip(i), |sf, i| sf.unskip(i));
+    });
+    if shared.scenario.density > 0 {
+        group.bench_function(format!("{name}/unskip"), |b| {
+            let sf = field.get_or_insert_with(|| T::from_source(shared.source()));
+            let indices = &shared.samples().1;
+            toggle(b, sf, indices, |sf, i| sf.unskip(i), |sf, i| sf.skip(i));
+        });
+    }
+}
+
+// times `op` over the sampled indices, undoing each batch untimed so the
+// field keeps the scenario's shape
+fn toggle<T>(
+    b: &mut criterion::Bencher<'_, WallTime>,
+    sf: &mut T,
+    indices: &[usize],
+    op: impl Fn(&mut T, usize),
+    undo: impl Fn(&mut T, usize),
+) {
+    if indices.is_empty() {
+        // a tiny field can end up with nothing to toggle
+        b.iter(|| ());
+        return;
+    }
+    b.iter_custom(|iters| {
+        let mut elapsed = Duration::ZERO;
+        let mut done = 0;
+        while done < iters {
+            let batch = &indices[..(iters - done).min(indices.len() as u64) as usize];
+            let start = Instant::now();
+            for &i in batch {
+                op(black_box(&mut *sf), i);
+            }
+            elapsed += start.elapsed();
+            for &i in batch.iter().rev() {
+                undo(sf, i);
+            }
+            done += batch.len() as u64;
+        }
+        elapsed
+    });
+}
+
+fn bench_matrix(c: &mut Criterion) {
+    let sizes: Vec<usize> = match std::env::var("SKIPFIELD_BENCH_SIZES") {
+        Ok(sizes) => sizes.split(',').map(|s| s.trim().parse().expect("sizes are integers")).collect(),
+        Err(_) => SIZES.to_vec(),
+    };
+
+    for &len in &sizes {
+        for pattern in Pattern::ALL {
+            for density in DENSITIES {
+                let scenario = Scenario { len, pattern, density };
+                let mut shared = Shared { scenario: &scenario, source: None, samples: None };
+                let mut group = c.benchmark_group(format!("{len}/{}/{density}%", pattern.name()));
+                bench_subject::<BoolSkipfield>(&mut group, &mut shared);
+                bench_subject::<boolean_skipfield::BoolSkipfield>(&mut group, &mut shared);
+                bench_subject::<BitmaskSkipfield>(&mut group, &mut shared);
+                bench_subject::<ChainedLcjc>(&mut group, &mut shared);
+                bench_subject::<LCJCSkipfield<u32>>(&mut group, &mut shared);
+                bench_subject::<HCJCSkipfield<u32>>(&mut group, &mut shared);
+                bench_subject::<CompressedSkipfield>(&mut group, &mut shared);
+                bench_subject::<LockLessBoolSkipfield>(&mut group, &mut shared);
+                bench_subject::<LockLessBitmaskSkipfield>(&mut group, &mut shared);
+                bench_subject::<optimized_seq_skipfield::Skipfield>(&mut group, &mut shared);
+                group.finish();
+            }
+        }
+    }
+}
+
+criterion_group! {
+    name = matrix;
+    // the full matrix has thousands of entries, so keep each one short
+    config = Criterion::default()
+        .sample_size(10)
+        .warm_up_time(Duration::from_millis(200))
+        .measurement_time(Duration::from_secs(1));
+    targets = bench_matrix
+}
+criterion_main!(matrix);
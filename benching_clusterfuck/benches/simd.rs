@@ -0,0 +1,102 @@
+use criterion::{criterion_group, criterion_main, Criterion, black_box};
+use benching_clusterfuck::{
+    bitmask_skipfield::BitmaskSkipfield,
+    bool_skipfield::BoolSkipfield,
+    simd,
+};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+const N: usize = 1_000_000;
+const SKIP_RATIO: f64 = 0.3;
+
+fn random_flags() -> Vec<bool> {
+    let mut rng = StdRng::seed_from_u64(42);
+    (0..N).map(|_| rng.random::<f64>() < SKIP_RATIO).collect()
+}
+
+fn bench_count(c: &mut Criterion) {
+    let flags = random_flags();
+    let words = simd::pack_bools(&flags);
+
+    c.bench_function("count_true scalar", |b| {
+        b.iter(|| black_box(black_box(&flags).iter().filter(|&&f| f).count()));
+    });
+    c.bench_function("count_true simd", |b| {
+        b.iter(|| black_box(simd::count_true(black_box(&flags))));
+    });
+    c.bench_function("popcount scalar", |b| {
+        b.iter(|| black_box(black_box(&words).iter().map(|w| w.count_ones() as usize).sum::<usize>()));
+    });
+    c.bench_function("popcount simd", |b| {
+        b.iter(|| black_box(simd::popcount(black_box(&words))));
+    });
+}
+
+fn bench_first_active(c: &mut Criterion) {
+    // only the last slot is active, so the whole field gets scanned
+    let mut flags = vec![true; N];
+    flags[N - 1] = false;
+    let mut words = vec![u64::MAX; N / 64];
+    words[N / 64 - 1] = 0;
+
+    c.bench_function("first_false scalar", |b| {
+        b.iter(|| black_box(black_box(&flags).iter().position(|&f| !f)));
+    });
+    c.bench_function("first_false simd", |b| {
+        b.iter(|| black_box(simd::first_false(black_box(&flags))));
+    });
+    c.bench_function("first_word_not scalar", |b| {
+        b.iter(|| black_box(black_box(&words).iter().position(|&w| w != u64::MAX)));
+    });
+    c.bench_function("first_word_not simd", |b| {
+        b.iter(|| black_box(simd::first_word_not(black_box(&words), u64::MAX)));
+    });
+}
+
+fn bench_pack(c: &mut Criterion) {
+    let flags = random_flags();
+
+    c.bench_function("pack_bools scalar", |b| {
+        b.iter(|| {
+            let packed: Vec<u64> = black_box(&flags)
+                .chunks(64)
+                .map(|chunk| chunk.iter().enumerate().fold(0u64, |w, (i, &f)| w | ((f as u64) << i)))
+                .collect();
+            black_box(packed);
+        });
+    });
+    c.bench_function("pack_bools simd", |b| {
+        b.iter(|| black_box(simd::pack_bools(black_box(&flags))));
+    });
+}
+
+fn bench_skipfields(c: &mut Criterion) {
+    let flags = random_flags();
+    let mut bool_sf = BoolSkipfield::new(N);
+    for (i, &f) in flags.iter().enumerate() {
+        if f {
+            bool_sf.skip(i);
+        }
+    }
+    let bitmask_sf = BitmaskSkipfield::from_flags(&flags);
+
+    c.bench_function("BoolSkipfield::count_skipped", |b| {
+        b.iter(|| black_box(bool_sf.count_skipped()));
+    });
+    c.bench_function("BitmaskSkipfield::count_skipped", |b| {
+        b.iter(|| black_box(bitmask_sf.count_skipped()));
+    });
+    c.bench_function("BitmaskSkipfield::from_flags", |b| {
+        b.iter(|| black_box(BitmaskSkipfield::from_flags(black_box(&flags))));
+    });
+}
+
+criterion_group!(
+    simd_benches,
+    bench_count,
+    bench_first_active,
+    bench_pack,
+    bench_skipfields
+);
+criterion_main!(simd_benches);
@@ -0,0 +1,101 @@
+//! Replays a recorded trace against each skipfield representation, reporting
+//! how long each took and whether they all gave the same answers and ended
+//! in the same state.
+//!
+//!     cargo run --release --bin replay -- app.trace [--repeat N]
+
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use benching_clusterfuck::bitmask_skipfield::BitmaskSkipfield;
+use benching_clusterfuck::bool_skipfield::BoolSkipfield;
+use benching_clusterfuck::lcjc_skipfield::LCJCSkipfield;
+use benching_clusterfuck::trace::{self, Answer, Trace};
+use skipfield_core::Skipfield;
+
+struct Run {
+    name: &'static str,
+    // fastest of the repeats
+    time: Duration,
+    answers: Vec<Answer>,
+    // the skipped slots once the trace has run
+    state: Answer,
+}
+
+fn run<T: Skipfield>(name: &'static str, trace: &Trace, repeat: usize, new: impl Fn(usize) -> T) -> Run {
+    let mut time = Duration::MAX;
+    let (mut answers, mut state) = (Vec::new(), Answer::Indices { count: 0, hash: 0 });
+    for _ in 0..repeat {
+        let mut sf = new(trace.len);
+        let start = Instant::now();
+        answers = trace.replay(&mut sf);
+        time = time.min(start.elapsed());
+        state = trace::fingerprint(&sf);
+    }
+    Run { name, time, answers, state }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (path, repeat) = match &args[..] {
+        [path] => (path, 1),
+        [path, flag, n] if flag == "--repeat" => match n.parse() {
+            Ok(n) if n > 0 => (path, n),
+            _ => {
+                eprintln!("--repeat takes a positive count");
+                return ExitCode::FAILURE;
+            }
+        },
+        _ => {
+            eprintln!("usage: replay <trace-file> [--repeat N]");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let trace = match std::fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|text| {
+        Trace::parse(&text).map_err(|e| e.to_string())
+    }) {
+        Ok(trace) => trace,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    println!(
+        "{path}: {} slots, {} ops ({} queries), best of {repeat}",
+        trace.len,
+        trace.ops.len(),
+        trace.queries().count()
+    );
+
+    // the chained u8 field can hold any trace, however long its runs get
+    let runs = [
+        run("bool", &trace, repeat, BoolSkipfield::new),
+        run("bitmask", &trace, repeat, BitmaskSkipfield::new),
+        run("lcjc", &trace, repeat, LCJCSkipfield::new_chained),
+    ];
+    for r in &runs {
+        let per_op = r.time.as_nanos() as f64 / trace.ops.len().max(1) as f64;
+        println!("{:<8} {:>12.3?} {:>10.1} ns/op", r.name, r.time, per_op);
+    }
+
+    let reference = &runs[0];
+    for (k, op) in trace.queries().enumerate() {
+        if runs.iter().any(|r| r.answers[k] != reference.answers[k]) {
+            println!("results differ at query {k} (`{op}`):");
+            for r in &runs {
+                println!("  {:<8} {:?}", r.name, r.answers[k]);
+            }
+            return ExitCode::FAILURE;
+        }
+    }
+    if runs.iter().any(|r| r.state != reference.state) {
+        println!("final states differ:");
+        for r in &runs {
+            println!("  {:<8} {:?}", r.name, r.state);
+        }
+        return ExitCode::FAILURE;
+    }
+    println!("results identical across {} skipfields", runs.len());
+    ExitCode::SUCCESS
+}
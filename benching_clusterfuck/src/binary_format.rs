@@ -0,0 +1,577 @@
+//! Self-describing binary format for skipfields, with no dependencies.
+//!
+//! ```text
+//! magic     4 bytes   b"SKPF"
+//! version   u8        currently 1
+//! tag       u8        representation that wrote the file, see `ReprTag`
+//! encoding  u8        how the payload is stored, see `Encoding`
+//! len       u64 LE    number of slots
+//! payload   ...
+//! checksum  u32 LE    CRC-32 of every byte before it
+//! ```
+//!
+//! Writers pick whichever payload encoding comes out smallest. Readers accept
+//! every encoding and every tag, so a file written from a `BitmaskSkipfield`
+//! loads into an `LCJCSkipfield` and back.
+
+use std::fmt;
+
+use skipfield_core::Skipfield;
+
+use crate::bitmask_skipfield::BitmaskSkipfield;
+use crate::lcjc_skipfield::{active_gaps, LCJCNode, LCJCSkipfield};
+
+const MAGIC: &[u8; 4] = b"SKPF";
+const VERSION: u8 = 1;
+// magic, version, tag, encoding, len
+const HEADER_LEN: usize = 4 + 1 + 1 + 1 + 8;
+
+/// Largest field `from_bytes` and `decode` build from a run or sparse
+/// payload. Those stay tiny however long the field is, so without a cap a
+/// few crafted bytes could ask for any amount of memory. At 4M slots the
+/// worst case is a 32 MiB `LCJCSkipfield<u64>`; pass a larger cap to
+/// `from_bytes_with_max_len` for bigger fields. Raw words payloads are as big
+/// as the field itself and aren't capped.
+pub const DEFAULT_MAX_LEN: usize = 1 << 22;
+
+/// Which skipfield wrote a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReprTag {
+    Bitmask = 0,
+    Lcjc = 1,
+    /// An LCJC field that splits long runs. Loading one into an LCJC field
+    /// gives a chained field again.
+    LcjcChained = 2,
+}
+
+/// How the payload stores the skipped slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// One bit per slot in `u64` LE words, set if skipped, padding set.
+    RawWords = 0,
+    /// Varint run count, then a varint `(gap, len)` pair per skipped run. The
+    /// gap counts active slots since the previous run.
+    Runs = 1,
+    /// Varint count, then the skipped indices as varint deltas.
+    SparseSkipped = 2,
+    /// Varint count, then the active indices as varint deltas.
+    SparseActive = 3,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnknownTag(u8),
+    UnknownEncoding(u8),
+    Truncated,
+    ChecksumMismatch { stored: u32, computed: u32 },
+    /// The payload decodes but breaks the format's rules.
+    Corrupt(&'static str),
+    /// A skipped run is longer than the target's node type can hold.
+    RunTooLong { len: usize, max: usize },
+    /// The field is longer than the reader allows for its encoding.
+    LenTooLarge { len: usize, max: usize },
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::BadMagic => write!(f, "not a skipfield file"),
+            FormatError::UnsupportedVersion(v) => write!(f, "unsupported format version {v}"),
+            FormatError::UnknownTag(t) => write!(f, "unknown representation tag {t}"),
+            FormatError::UnknownEncoding(e) => write!(f, "unknown payload encoding {e}"),
+            FormatError::Truncated => write!(f, "file is truncated"),
+            FormatError::ChecksumMismatch { stored, computed } => {
+                write!(f, "checksum mismatch: stored {stored:#010x}, computed {computed:#010x}")
+            }
+            FormatError::Corrupt(why) => write!(f, "corrupt payload: {why}"),
+            FormatError::RunTooLong { len, max } => {
+                write!(f, "skipped run of {len} slots doesn't fit a node of max {max}")
+            }
+            FormatError::LenTooLarge { len, max } => write!(f, "field of {len} slots is over the limit of {max}"),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// Header and skipped runs of a decoded file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoded {
+    pub tag: ReprTag,
+    pub encoding: Encoding,
+    pub len: usize,
+    /// Maximal skipped runs as `(start, len)`, ascending.
+    pub runs: Vec<(usize, usize)>,
+}
+
+impl BitmaskSkipfield {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let runs: Vec<(usize, usize)> = self.skipped_runs().collect();
+        encode(ReprTag::Bitmask, Skipfield::len(self), &runs)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+        Self::from_bytes_with_max_len(bytes, DEFAULT_MAX_LEN)
+    }
+
+    /// Like `from_bytes`, with a caller-chosen cap instead of
+    /// `DEFAULT_MAX_LEN`.
+    pub fn from_bytes_with_max_len(bytes: &[u8], max_len: usize) -> Result<Self, FormatError> {
+        let decoded = decode_with_max_len(bytes, max_len)?;
+        let mut sf = BitmaskSkipfield::new(decoded.len);
+        for (start, len) in decoded.runs {
+            sf.skip_range(start..start + len);
+        }
+        Ok(sf)
+    }
+}
+
+impl<N: LCJCNode> LCJCSkipfield<N> {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let tag = if self.is_chained() { ReprTag::LcjcChained } else { ReprTag::Lcjc };
+        let runs: Vec<(usize, usize)> = self.skipped_runs().collect();
+        encode(tag, Skipfield::len(self), &runs)
+    }
+
+    /// Fails with `RunTooLong` if a run doesn't fit `N` and the file wasn't
+    /// written by a chained field.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+        Self::from_bytes_with_max_len(bytes, DEFAULT_MAX_LEN)
+    }
+
+    /// Like `from_bytes`, with a caller-chosen cap instead of
+    /// `DEFAULT_MAX_LEN`.
+    pub fn from_bytes_with_max_len(bytes: &[u8], max_len: usize) -> Result<Self, FormatError> {
+        let decoded = decode_with_max_len(bytes, max_len)?;
+        let mut sf = if decoded.tag == ReprTag::LcjcChained {
+            Self::with_len_chained(decoded.len)
+        } else {
+            if let Some(&(_, len)) = decoded.runs.iter().find(|&&(_, len)| len > N::MAX) {
+                return Err(FormatError::RunTooLong { len, max: N::MAX });
+            }
+            Self::with_len(decoded.len)
+        };
+        for (start, len) in decoded.runs {
+            sf.skip_range(start..start + len);
+        }
+        Ok(sf)
+    }
+}
+
+/// Writes a file for a field of `len` slots with the given maximal skipped
+/// runs, choosing the smallest encoding.
+pub fn encode(tag: ReprTag, len: usize, runs: &[(usize, usize)]) -> Vec<u8> {
+    let raw = encode_raw(len, runs);
+    let mut best = (Encoding::RawWords, raw);
+    let candidates: [(Encoding, PayloadEncoder); 3] = [
+        (Encoding::Runs, encode_runs),
+        (Encoding::SparseSkipped, encode_sparse_skipped),
+        (Encoding::SparseActive, encode_sparse_active),
+    ];
+    for (encoding, encoder) in candidates {
+        if let Some(payload) = encoder(len, runs, best.1.len()) {
+            best = (encoding, payload);
+        }
+    }
+
+    let (encoding, payload) = best;
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len() + 4);
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    out.push(tag as u8);
+    out.push(encoding as u8);
+    out.extend_from_slice(&(len as u64).to_le_bytes());
+    out.extend_from_slice(&payload);
+    out.extend_from_slice(&crc32(&out).to_le_bytes());
+    out
+}
+
+/// Checks and decodes a file, capping run and sparse payloads at
+/// `DEFAULT_MAX_LEN` slots.
+pub fn decode(bytes: &[u8]) -> Result<Decoded, FormatError> {
+    decode_with_max_len(bytes, DEFAULT_MAX_LEN)
+}
+
+/// Checks and decodes a file, failing with `LenTooLarge` if a run or sparse
+/// payload describes more than `max_len` slots.
+pub fn decode_with_max_len(bytes: &[u8], max_len: usize) -> Result<Decoded, FormatError> {
+    if bytes.len() < 4 || &bytes[..4] != MAGIC {
+        return Err(FormatError::BadMagic);
+    }
+    if bytes.len() < HEADER_LEN + 4 {
+        return Err(FormatError::Truncated);
+    }
+    let (body, stored) = bytes.split_at(bytes.len() - 4);
+    let stored = u32::from_le_bytes(stored.try_into().unwrap());
+    let computed = crc32(body);
+    if stored != computed {
+        return Err(FormatError::ChecksumMismatch { stored, computed });
+    }
+
+    if body[4] != VERSION {
+        return Err(FormatError::UnsupportedVersion(body[4]));
+    }
+    let tag = match body[5] {
+        0 => ReprTag::Bitmask,
+        1 => ReprTag::Lcjc,
+        2 => ReprTag::LcjcChained,
+        t => return Err(FormatError::UnknownTag(t)),
+    };
+    let encoding = match body[6] {
+        0 => Encoding::RawWords,
+        1 => Encoding::Runs,
+        2 => Encoding::SparseSkipped,
+        3 => Encoding::SparseActive,
+        e => return Err(FormatError::UnknownEncoding(e)),
+    };
+    let len = u64::from_le_bytes(body[7..HEADER_LEN].try_into().unwrap());
+    let len = usize::try_from(len).map_err(|_| FormatError::Corrupt("length doesn't fit in usize"))?;
+    if encoding != Encoding::RawWords && len > max_len {
+        return Err(FormatError::LenTooLarge { len, max: max_len });
+    }
+
+    let mut reader = Reader { bytes: &body[HEADER_LEN..] };
+    let runs = match encoding {
+        Encoding::RawWords => decode_raw(&mut reader, len)?,
+        Encoding::Runs => decode_runs(&mut reader, len)?,
+        Encoding::SparseSkipped => indices_to_runs(decode_indices(&mut reader, len)?),
+        Encoding::SparseActive => {
+            let active = indices_to_runs(decode_indices(&mut reader, len)?);
+            active_gaps(active.into_iter(), len).collect()
+        }
+    };
+    if !reader.bytes.is_empty() {
+        return Err(FormatError::Corrupt("trailing bytes after payload"));
+    }
+    Ok(Decoded { tag, encoding, len, runs })
+}
+
+fn encode_raw(len: usize, runs: &[(usize, usize)]) -> Vec<u8> {
+    let num_words = len.div_ceil(64);
+    let mut words = vec![0u64; num_words];
+    for &(start, run_len) in runs {
+        for i in start..start + run_len {
+            words[i / 64] |= 1 << (i % 64);
+        }
+    }
+    let tail_bits = len % 64;
+    if tail_bits > 0 {
+        words[num_words - 1] |= u64::MAX << tail_bits;
+    }
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+// the encoders below give up once they grow past `limit` bytes
+type PayloadEncoder = fn(usize, &[(usize, usize)], usize) -> Option<Vec<u8>>;
+
+fn encode_runs(_len: usize, runs: &[(usize, usize)], limit: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    write_varint(&mut out, runs.len() as u64);
+    let mut prev_end = 0;
+    for &(start, run_len) in runs {
+        write_varint(&mut out, (start - prev_end) as u64);
+        write_varint(&mut out, run_len as u64);
+        prev_end = start + run_len;
+        if out.len() >= limit {
+            return None;
+        }
+    }
+    (out.len() < limit).then_some(out)
+}
+
+fn encode_sparse_skipped(_len: usize, runs: &[(usize, usize)], limit: usize) -> Option<Vec<u8>> {
+    let count: usize = runs.iter().map(|&(_, run_len)| run_len).sum();
+    if count >= limit {
+        return None;
+    }
+    encode_indices(count, runs.iter().flat_map(|&(start, run_len)| start..start + run_len), limit)
+}
+
+fn encode_sparse_active(len: usize, runs: &[(usize, usize)], limit: usize) -> Option<Vec<u8>> {
+    let count = len - runs.iter().map(|&(_, run_len)| run_len).sum::<usize>();
+    if count >= limit {
+        return None;
+    }
+    let active = active_gaps(runs.iter().copied(), len).flat_map(|(start, run_len)| start..start + run_len);
+    encode_indices(count, active, limit)
+}
+
+// each index is stored as its distance past the previous one, so the common
+// case of nearby indices takes one byte
+fn encode_indices(count: usize, indices: impl Iterator<Item = usize>, limit: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    write_varint(&mut out, count as u64);
+    let mut next = 0;
+    for i in indices {
+        write_varint(&mut out, (i - next) as u64);
+        next = i + 1;
+        if out.len() >= limit {
+            return None;
+        }
+    }
+    (out.len() < limit).then_some(out)
+}
+
+fn decode_raw(reader: &mut Reader, len: usize) -> Result<Vec<(usize, usize)>, FormatError> {
+    let num_words = len.div_ceil(64);
+    let chunks: Vec<u64> = reader
+        .take(num_words * 8)?
+        .chunks_exact(8)
+        .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+        .collect();
+    let tail_bits = len % 64;
+    if tail_bits > 0 {
+        let padding = u64::MAX << tail_bits;
+        if chunks[num_words - 1] & padding != padding {
+            return Err(FormatError::Corrupt("padding bits past the last slot must be set"));
+        }
+    }
+    Ok(BitmaskSkipfield::from_words(chunks, len).skipped_runs().collect())
+}
+
+fn decode_runs(reader: &mut Reader, len: usize) -> Result<Vec<(usize, usize)>, FormatError> {
+    let count = reader.varint()?;
+    let mut runs = Vec::new();
+    let mut prev_end = 0usize;
+    for run_i in 0..count {
+        let gap = reader.varint()?;
+        let run_len = reader.varint()?;
+        if run_len == 0 {
+            return Err(FormatError::Corrupt("empty run"));
+        }
+        if gap == 0 && run_i > 0 {
+            return Err(FormatError::Corrupt("runs must be separated by an active slot"));
+        }
+        let start = prev_end.checked_add(gap).ok_or(FormatError::Corrupt("run past the end"))?;
+        let end = start.checked_add(run_len).filter(|&end| end <= len);
+        prev_end = end.ok_or(FormatError::Corrupt("run past the end"))?;
+        runs.push((start, run_len));
+    }
+    Ok(runs)
+}
+
+fn decode_indices(reader: &mut Reader, len: usize) -> Result<Vec<usize>, FormatError> {
+    let count = reader.varint()?;
+    if count > len {
+        return Err(FormatError::Corrupt("more indices than slots"));
+    }
+    // every index takes at least a byte
+    if count > reader.bytes.len() {
+        return Err(FormatError::Truncated);
+    }
+    let mut indices = Vec::new();
+    let mut next = 0usize;
+    for _ in 0..count {
+        let i = next.checked_add(reader.varint()?).filter(|&i| i < len);
+        let i = i.ok_or(FormatError::Corrupt("index past the end"))?;
+        indices.push(i);
+        next = i + 1;
+    }
+    Ok(indices)
+}
+
+// groups ascending indices into `(start, len)` runs
+fn indices_to_runs(indices: Vec<usize>) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for i in indices {
+        match runs.last_mut() {
+            Some((start, len)) if *start + *len == i => *len += 1,
+            _ => runs.push((i, 1)),
+        }
+    }
+    runs
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], FormatError> {
+        if self.bytes.len() < n {
+            return Err(FormatError::Truncated);
+        }
+        let (head, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    // LEB128, at most 10 bytes for a u64
+    fn varint(&mut self) -> Result<usize, FormatError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return usize::try_from(value).map_err(|_| FormatError::Corrupt("varint doesn't fit in usize"));
+            }
+        }
+        Err(FormatError::Corrupt("varint too long"))
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+// CRC-32 as used by zip and png
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes
+        .iter()
+        .fold(!0u32, |crc, &b| (crc >> 8) ^ CRC_TABLE[((crc ^ b as u32) & 0xff) as usize])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoding_of(bytes: &[u8]) -> Encoding {
+        decode(bytes).unwrap().encoding
+    }
+
+    #[test]
+    fn test_crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_picks_smallest_encoding() {
+        let mut sf = BitmaskSkipfield::new(10_000);
+        sf.skip(5);
+        sf.skip(9_000);
+        let bytes = sf.to_bytes();
+        assert_eq!(encoding_of(&bytes), Encoding::SparseSkipped);
+        assert!(bytes.len() < 30);
+
+        sf.skip_range(0..10_000);
+        sf.unskip(7_777);
+        assert_eq!(encoding_of(&sf.to_bytes()), Encoding::SparseActive);
+
+        sf.unskip_range(0..10_000);
+        sf.skip_range(1_000..4_000);
+        sf.skip_range(6_000..9_000);
+        assert_eq!(encoding_of(&sf.to_bytes()), Encoding::Runs);
+
+        let random: Vec<bool> = (0..10_000u64).map(|i| (i * 0x9e37_79b9) >> 7 & 1 == 1).collect();
+        assert_eq!(encoding_of(&BitmaskSkipfield::from_flags(&random).to_bytes()), Encoding::RawWords);
+    }
+
+    #[test]
+    fn test_every_encoding_roundtrips_across_representations() {
+        let random: Vec<bool> = (0..1_000u64).map(|i| (i * 0x9e37_79b9) >> 7 & 1 == 1).collect();
+        let mut fields = vec![BitmaskSkipfield::from_flags(&random), BitmaskSkipfield::new(130)];
+        let mut sparse = BitmaskSkipfield::new(130);
+        sparse.skip(64);
+        fields.push(sparse.clone());
+        fields.push(!&sparse);
+
+        for sf in fields {
+            let bytes = sf.to_bytes();
+            let expected: Vec<(usize, usize)> = sf.skipped_runs().collect();
+
+            let lcjc: LCJCSkipfield<u16> = LCJCSkipfield::from_bytes(&bytes).unwrap();
+            assert_eq!(lcjc.skipped_runs().collect::<Vec<_>>(), expected);
+            let back = BitmaskSkipfield::from_bytes(&lcjc.to_bytes()).unwrap();
+            assert_eq!(back.skipped_runs().collect::<Vec<_>>(), expected);
+            assert_eq!(Skipfield::len(&back), Skipfield::len(&sf));
+        }
+    }
+
+    #[test]
+    fn test_lcjc_node_limit() {
+        let mut sf = BitmaskSkipfield::new(1_000);
+        sf.skip_range(100..700);
+        let bytes = sf.to_bytes();
+        assert_eq!(
+            LCJCSkipfield::<u8>::from_bytes(&bytes).err(),
+            Some(FormatError::RunTooLong { len: 600, max: 255 })
+        );
+
+        let mut chained = LCJCSkipfield::new_chained(1_000);
+        chained.skip_range(100..700);
+        let loaded = LCJCSkipfield::<u8>::from_bytes(&chained.to_bytes()).unwrap();
+        assert!(loaded.is_chained());
+        assert_eq!(loaded.count_skipped(), 600);
+    }
+
+    #[test]
+    fn test_detects_corruption() {
+        let mut sf = BitmaskSkipfield::new(500);
+        sf.skip_range(10..20);
+        let bytes = sf.to_bytes();
+
+        for i in 0..bytes.len() {
+            let mut broken = bytes.clone();
+            broken[i] ^= 0x10;
+            assert!(BitmaskSkipfield::from_bytes(&broken).is_err(), "flip at byte {i} went unnoticed");
+        }
+        assert_eq!(BitmaskSkipfield::from_bytes(&bytes[..10]).err(), Some(FormatError::Truncated));
+        assert_eq!(BitmaskSkipfield::from_bytes(b"nope").err(), Some(FormatError::BadMagic));
+
+        // a well-formed file with a payload that breaks the rules
+        let mut bad = bytes[..HEADER_LEN].to_vec();
+        bad[6] = Encoding::Runs as u8;
+        write_varint(&mut bad, 1);
+        write_varint(&mut bad, 495);
+        write_varint(&mut bad, 10);
+        bad.extend_from_slice(&crc32(&bad).to_le_bytes());
+        assert_eq!(BitmaskSkipfield::from_bytes(&bad).err(), Some(FormatError::Corrupt("run past the end")));
+    }
+
+    #[test]
+    fn test_crafted_header_cannot_demand_huge_allocations() {
+        // 25 bytes claiming 2^40 skipped slots
+        let mut bad = MAGIC.to_vec();
+        bad.extend_from_slice(&[VERSION, ReprTag::Bitmask as u8, Encoding::SparseSkipped as u8]);
+        bad.extend_from_slice(&(1u64 << 40).to_le_bytes());
+        write_varint(&mut bad, 1 << 40);
+        bad.extend_from_slice(&crc32(&bad).to_le_bytes());
+        assert_eq!(bad.len(), 25);
+
+        let too_large = FormatError::LenTooLarge { len: 1 << 40, max: DEFAULT_MAX_LEN };
+        assert_eq!(BitmaskSkipfield::from_bytes(&bad).err(), Some(too_large.clone()));
+        assert_eq!(LCJCSkipfield::<u32>::from_bytes(&bad).err(), Some(too_large));
+        assert_eq!(decode_with_max_len(&bad, usize::MAX).err(), Some(FormatError::Truncated));
+
+        let mut sf = BitmaskSkipfield::new(1_000);
+        sf.skip(3);
+        let bytes = sf.to_bytes();
+        assert_eq!(
+            BitmaskSkipfield::from_bytes_with_max_len(&bytes, 999).err(),
+            Some(FormatError::LenTooLarge { len: 1_000, max: 999 })
+        );
+        assert!(BitmaskSkipfield::from_bytes_with_max_len(&bytes, 1_000).is_ok());
+
+        // 20 bytes claiming 2^32 slots and no skipped runs
+        let mut bad = MAGIC.to_vec();
+        bad.extend_from_slice(&[VERSION, ReprTag::Lcjc as u8, Encoding::Runs as u8]);
+        bad.extend_from_slice(&(1u64 << 32).to_le_bytes());
+        write_varint(&mut bad, 0);
+        bad.extend_from_slice(&crc32(&bad).to_le_bytes());
+        assert_eq!(bad.len(), 20);
+        assert!(matches!(LCJCSkipfield::<u64>::from_bytes(&bad), Err(FormatError::LenTooLarge { .. })));
+        assert!(matches!(BitmaskSkipfield::from_bytes(&bad), Err(FormatError::LenTooLarge { .. })));
+    }
+}
@@ -1,11 +1,30 @@
+use std::iter::FusedIterator;
+use std::ops::{
+    BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Range, Sub, SubAssign,
+};
+
+use skipfield_core::Skipfield;
+
+use crate::simd;
+use crate::summary::ChunkSummary;
+
+const WORDS_PER_SUPERBLOCK: usize = 8;
+
+#[derive(Clone)]
 pub struct BitmaskSkipfield {
     chunks: Vec<u64>,
     len: usize,
+    // active slots before each superblock of `WORDS_PER_SUPERBLOCK` chunks,
+    // plus the total at the end. only present after `build_rank_directory`
+    rank_dir: Option<Vec<usize>>,
+    // which chunks have an active slot, so searches can jump over fully
+    // skipped stretches
+    summary: ChunkSummary,
 }
 
 impl BitmaskSkipfield {
     pub fn new(len: usize) -> Self {
-        let num_chunks = (len + 63) / 64;
+        let num_chunks = len.div_ceil(64);
         let mut chunks = vec![0u64; num_chunks];
     
         let extra_bits = (num_chunks * 64).saturating_sub(len);
@@ -14,17 +33,184 @@ impl BitmaskSkipfield {
             chunks[num_chunks - 1] |= mask;
         }
     
-        Self { chunks, len }
+        let summary = ChunkSummary::new(num_chunks, |_| len > 0);
+        Self { chunks, len, rank_dir: None, summary }
+    }
+
+    /// Builds a field from one flag per slot, `true` meaning skipped. Packs
+    /// 64 flags per word.
+    pub fn from_flags(flags: &[bool]) -> Self {
+        Self::from_words(simd::pack_bools(flags), flags.len())
+    }
+
+    /// Builds a field from raw words, bit `i % 64` of word `i / 64` set if slot
+    /// `i` is skipped. Bits past `len` are ignored.
+    pub fn from_words(chunks: Vec<u64>, len: usize) -> Self {
+        assert_eq!(chunks.len(), len.div_ceil(64), "{len} slots need {} words", len.div_ceil(64));
+        let summary = ChunkSummary::new(chunks.len(), |chunk_i| active_bits(&chunks, len, chunk_i) != 0);
+        let mut sf = Self { chunks, len, rank_dir: None, summary };
+        sf.set_padding();
+        sf
+    }
+
+    /// The raw words, laid out as `from_words` takes them. Bits past `len`
+    /// are always set.
+    pub fn words(&self) -> &[u64] {
+        &self.chunks
+    }
+
+    pub fn push(&mut self, skipped: bool) {
+        self.resize(self.len + 1, skipped);
+    }
+
+    /// Grows the field with slots in the given state, or truncates it.
+    pub fn resize(&mut self, new_len: usize, skipped: bool) {
+        if new_len <= self.len {
+            self.truncate(new_len);
+            return;
+        }
+
+        // the padding past `len` is already set, so every new slot starts out
+        // skipped and only needs clearing if it should be active
+        let old_len = self.len;
+        self.chunks.resize(new_len.div_ceil(64), u64::MAX);
+        self.len = new_len;
+        self.resize_summary();
+        if skipped {
+            self.rebuild_rank_dir_from(old_len / 64);
+        } else {
+            self.unskip_range(old_len..new_len);
+        }
+    }
+
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len >= self.len {
+            return;
+        }
+
+        self.chunks.truncate(new_len.div_ceil(64));
+        self.len = new_len;
+        self.set_padding();
+        self.resize_summary();
+        self.rebuild_rank_dir_from(new_len / 64);
+    }
+
+    /// Drops every skipped slot after the last active one.
+    pub fn trim_trailing_skipped(&mut self) {
+        let new_len = self.last_active().map_or(0, |i| i + 1);
+        self.truncate(new_len);
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = (self.len + additional).div_ceil(64);
+        self.chunks.reserve(needed.saturating_sub(self.chunks.len()));
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.chunks.shrink_to_fit();
+        if let Some(dir) = &mut self.rank_dir {
+            dir.shrink_to_fit();
+        }
+    }
+
+    // set algebra works on the sets of skipped slots: a slot of the union is
+    // skipped if it is skipped in either field
+
+    pub fn union_with(&mut self, other: &Self) {
+        self.combine_with(other, |a, b| a | b);
+    }
+
+    pub fn intersect_with(&mut self, other: &Self) {
+        self.combine_with(other, |a, b| a & b);
+    }
+
+    pub fn difference_with(&mut self, other: &Self) {
+        self.combine_with(other, |a, b| a & !b);
+    }
+
+    pub fn symmetric_difference_with(&mut self, other: &Self) {
+        self.combine_with(other, |a, b| a ^ b);
+    }
+
+    /// Swaps skipped and active slots.
+    pub fn invert(&mut self) {
+        for chunk in &mut self.chunks {
+            *chunk = !*chunk;
+        }
+        self.set_padding();
+        self.refresh_summary(0..self.chunks.len());
+        self.rebuild_rank_dir_from(0);
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut out = self.clone();
+        out.union_with(other);
+        out
+    }
+
+    pub fn intersect(&self, other: &Self) -> Self {
+        let mut out = self.clone();
+        out.intersect_with(other);
+        out
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut out = self.clone();
+        out.difference_with(other);
+        out
+    }
+
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        let mut out = self.clone();
+        out.symmetric_difference_with(other);
+        out
+    }
+
+    pub fn complement(&self) -> Self {
+        let mut out = self.clone();
+        out.invert();
+        out
+    }
+
+    fn combine_with(&mut self, other: &Self, op: impl Fn(u64, u64) -> u64) {
+        assert_eq!(self.len, other.len, "skipfields must have the same length");
+        for (a, &b) in self.chunks.iter_mut().zip(&other.chunks) {
+            *a = op(*a, b);
+        }
+        self.set_padding();
+        self.refresh_summary(0..self.chunks.len());
+        self.rebuild_rank_dir_from(0);
+    }
+
+    // marks the bits past `len` in the last chunk as skipped
+    fn set_padding(&mut self) {
+        let tail_bits = self.len % 64;
+        if tail_bits > 0 {
+            let last = self.chunks.len() - 1;
+            self.chunks[last] |= u64::MAX << tail_bits;
+        }
     }
 
     pub fn skip(&mut self, idx: usize) {
         let (chunk_idx, bit_idx) = Self::bit_pos(idx);
+        let was_active = self.chunks[chunk_idx] & (1 << bit_idx) == 0;
         self.chunks[chunk_idx] |= 1 << bit_idx;
+        if was_active {
+            self.update_rank_dir(chunk_idx, false);
+            if self.active_word(chunk_idx) == 0 {
+                self.refresh_summary(chunk_idx..chunk_idx + 1);
+            }
+        }
     }
 
     pub fn unskip(&mut self, idx: usize) {
         let (chunk_idx, bit_idx) = Self::bit_pos(idx);
+        let was_skipped = self.chunks[chunk_idx] & (1 << bit_idx) != 0;
         self.chunks[chunk_idx] &= !(1 << bit_idx);
+        if was_skipped {
+            self.update_rank_dir(chunk_idx, true);
+            self.refresh_summary(chunk_idx..chunk_idx + 1);
+        }
     }
 
     pub fn is_skipped(&self, idx: usize) -> bool {
@@ -33,41 +219,232 @@ impl BitmaskSkipfield {
     }
 
     pub fn first_active(&self) -> Option<usize> {
-        for (chunk_i, &chunk) in self.chunks.iter().enumerate() {
-            let inv = !chunk;
-            if inv != 0 {
-                return Some(chunk_i * 64 + inv.trailing_zeros() as usize);
+        self.find_from(0, false)
+    }
+
+    pub fn first_skipped(&self) -> Option<usize> {
+        self.find_from(0, true)
+    }
+
+    pub fn last_active(&self) -> Option<usize> {
+        self.prev_active_before(self.len)
+    }
+
+    /// Smallest active index strictly greater than `idx`.
+    pub fn next_active_after(&self, idx: usize) -> Option<usize> {
+        self.find_from(idx.checked_add(1)?, false)
+    }
+
+    /// Largest active index strictly less than `idx`.
+    pub fn prev_active_before(&self, idx: usize) -> Option<usize> {
+        let end = idx.min(self.len);
+        if end == 0 {
+            return None;
+        }
+        let (chunk_i, bit_idx) = Self::bit_pos(end - 1);
+        let word = self.active_word(chunk_i) & (u64::MAX >> (63 - bit_idx));
+        if word != 0 {
+            return Some(chunk_i * 64 + 63 - word.leading_zeros() as usize);
+        }
+        let chunk_i = self.summary.prev_before(chunk_i)?;
+        Some(chunk_i * 64 + 63 - self.active_word(chunk_i).leading_zeros() as usize)
+    }
+
+    /// Active index closest to `idx`, preferring the lower one on ties.
+    pub fn nearest_active(&self, idx: usize) -> Option<usize> {
+        if idx < self.len && !self.is_skipped(idx) {
+            return Some(idx);
+        }
+        match (self.prev_active_before(idx), self.next_active_after(idx)) {
+            (Some(p), Some(n)) => Some(if idx - p <= n - idx { p } else { n }),
+            (p, n) => p.or(n),
+        }
+    }
+
+    /// Enables O(1) `rank` and binary-searched `select`. The directory is kept
+    /// in sync on every change, which makes a single `skip`/`unskip` that flips
+    /// a bit O(len / 512).
+    pub fn build_rank_directory(&mut self) {
+        self.rank_dir = Some(Vec::new());
+        self.rebuild_rank_dir_from(0);
+    }
+
+    pub fn drop_rank_directory(&mut self) {
+        self.rank_dir = None;
+    }
+
+    pub fn has_rank_directory(&self) -> bool {
+        self.rank_dir.is_some()
+    }
+
+    /// Number of active slots in `0..idx`.
+    pub fn rank(&self, idx: usize) -> usize {
+        let idx = idx.min(self.len);
+        let Some(dir) = &self.rank_dir else {
+            return self.count_active_in(0..idx);
+        };
+
+        let (chunk_idx, bit_idx) = Self::bit_pos(idx);
+        let superblock = chunk_idx / WORDS_PER_SUPERBLOCK;
+        let mut count = dir[superblock];
+        for chunk_i in superblock * WORDS_PER_SUPERBLOCK..chunk_idx {
+            count += self.active_word(chunk_i).count_ones() as usize;
+        }
+        if bit_idx > 0 {
+            count += (self.active_word(chunk_idx) & ((1u64 << bit_idx) - 1)).count_ones() as usize;
+        }
+        count
+    }
+
+    /// Index of the `k`-th active slot, counting from zero.
+    pub fn select(&self, k: usize) -> Option<usize> {
+        let mut remaining = k;
+        let mut chunk_i = 0;
+        if let Some(dir) = &self.rank_dir {
+            if k >= dir[dir.len() - 1] {
+                return None;
+            }
+            // last superblock with fewer than k + 1 active slots before it
+            let superblock = dir.partition_point(|&before| before <= k) - 1;
+            remaining -= dir[superblock];
+            chunk_i = superblock * WORDS_PER_SUPERBLOCK;
+        }
+
+        while chunk_i < self.chunks.len() {
+            let mut word = self.active_word(chunk_i);
+            let ones = word.count_ones() as usize;
+            if remaining < ones {
+                for _ in 0..remaining {
+                    word &= word - 1;
+                }
+                return Some(chunk_i * 64 + word.trailing_zeros() as usize);
             }
+            remaining -= ones;
+            chunk_i += 1;
         }
         None
     }
 
+    /// Popcounts every chunk, then takes off the always-set padding bits.
     pub fn count_skipped(&self) -> usize {
-        let full_chunks = self.len / 64;
-        let tail_bits = self.len % 64;
-    
-        let mut count = self.chunks[..full_chunks]
-            .iter()
-            .map(|c| c.count_ones() as usize)
-            .sum::<usize>();
-    
-        if tail_bits > 0 {
-            let last_chunk = self.chunks[full_chunks];
-            let mask = (1u64 << tail_bits) - 1;
-            count += (last_chunk & mask).count_ones() as usize;
-        }
-    
-        count
+        simd::popcount(&self.chunks) - (self.chunks.len() * 64 - self.len)
     }
-    
+
+    /// Popcounts only the chunks the summary marks as holding an active slot.
     pub fn count_active(&self) -> usize {
-        self.len - self.count_skipped()
+        self.summary
+            .chunks()
+            .map(|chunk_i| self.active_word(chunk_i).count_ones() as usize)
+            .sum()
+    }
+
+    pub fn skip_range(&mut self, range: Range<usize>) {
+        assert!(range.end <= self.len, "range end {} out of bounds (len = {})", range.end, self.len);
+        let first_chunk = range.start / 64;
+        let end_chunk = range.end.div_ceil(64);
+        Self::for_each_range_chunk(range, |chunk_i, mask| self.chunks[chunk_i] |= mask);
+        self.refresh_summary(first_chunk..end_chunk);
+        self.rebuild_rank_dir_from(first_chunk);
+    }
+
+    pub fn unskip_range(&mut self, range: Range<usize>) {
+        assert!(range.end <= self.len, "range end {} out of bounds (len = {})", range.end, self.len);
+        let first_chunk = range.start / 64;
+        let end_chunk = range.end.div_ceil(64);
+        Self::for_each_range_chunk(range, |chunk_i, mask| self.chunks[chunk_i] &= !mask);
+        self.refresh_summary(first_chunk..end_chunk);
+        self.rebuild_rank_dir_from(first_chunk);
+    }
+
+    pub fn is_range_skipped(&self, range: Range<usize>) -> bool {
+        !self.any_active_in(range)
+    }
+
+    pub fn any_active_in(&self, range: Range<usize>) -> bool {
+        let mut any = false;
+        Self::for_each_range_chunk(range, |chunk_i, mask| any |= !self.chunks[chunk_i] & mask != 0);
+        any
+    }
+
+    pub fn count_active_in(&self, range: Range<usize>) -> usize {
+        let mut count = 0;
+        Self::for_each_range_chunk(range, |chunk_i, mask| {
+            count += (!self.chunks[chunk_i] & mask).count_ones() as usize;
+        });
+        count
     }
 
     pub fn active_indices_1(&self) -> impl Iterator<Item = usize> + '_ {
         (0..self.len).filter(move |&i| !self.is_skipped(i))
     }
 
+    pub fn skipped_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.chunks.len()).flat_map(move |chunk_i| {
+            let mut word = self.chunks[chunk_i] & valid_bits(self.len, chunk_i);
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let tz = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(chunk_i * 64 + tz)
+            })
+        })
+    }
+
+    /// Maximal runs of active slots as `(start, len)`.
+    pub fn active_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.runs(false)
+    }
+
+    /// Maximal runs of skipped slots as `(start, len)`.
+    pub fn skipped_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.runs(true)
+    }
+
+    fn runs(&self, skipped: bool) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let mut pos = 0;
+        std::iter::from_fn(move || {
+            let start = self.find_from(pos, skipped)?;
+            let end = self.find_from(start, !skipped).unwrap_or(self.len);
+            pos = end;
+            Some((start, end - start))
+        })
+    }
+
+    // first index at or after `from` that is skipped (or active), scanning a
+    // word at a time
+    fn find_from(&self, from: usize, skipped: bool) -> Option<usize> {
+        if from >= self.len {
+            return None;
+        }
+        let (mut chunk_i, bit_idx) = Self::bit_pos(from);
+        let word_at = |chunk_i: usize| {
+            if skipped {
+                self.chunks[chunk_i] & valid_bits(self.len, chunk_i)
+            } else {
+                self.active_word(chunk_i)
+            }
+        };
+        let mut word = word_at(chunk_i) & (u64::MAX << bit_idx);
+        loop {
+            if word != 0 {
+                return Some(chunk_i * 64 + word.trailing_zeros() as usize);
+            }
+            // active slots can jump straight to the next chunk holding one,
+            // skipped ones to the next chunk that isn't all zeroes
+            chunk_i = if skipped {
+                chunk_i + 1 + simd::first_word_not(&self.chunks[chunk_i + 1..], 0)?
+            } else {
+                self.summary.next_from(chunk_i + 1)?
+            };
+            if chunk_i >= self.chunks.len() {
+                return None;
+            }
+            word = word_at(chunk_i);
+        }
+    }
+
     pub fn active_indices_2(&self) -> impl Iterator<Item = usize> + '_ {
         self.chunks.iter().enumerate().flat_map(move |(chunk_i, &chunk)| {
             let mut inv = !chunk;
@@ -88,16 +465,106 @@ impl BitmaskSkipfield {
         (index / 64, index % 64)
     }
 
+    #[inline]
+    fn active_word(&self, chunk_i: usize) -> u64 {
+        active_bits(&self.chunks, self.len, chunk_i)
+    }
+
+    fn refresh_summary(&mut self, chunks: Range<usize>) {
+        let (words, len) = (&self.chunks, self.len);
+        self.summary.refresh(chunks, |chunk_i| active_bits(words, len, chunk_i) != 0);
+    }
+
+    fn resize_summary(&mut self) {
+        let (words, len) = (&self.chunks, self.len);
+        self.summary.resize(words.len(), |chunk_i| active_bits(words, len, chunk_i) != 0);
+    }
+
+    fn update_rank_dir(&mut self, chunk_i: usize, became_active: bool) {
+        if let Some(dir) = &mut self.rank_dir {
+            for before in &mut dir[chunk_i / WORDS_PER_SUPERBLOCK + 1..] {
+                if became_active {
+                    *before += 1;
+                } else {
+                    *before -= 1;
+                }
+            }
+        }
+    }
+
+    // recomputes every directory entry after the superblock holding `chunk_i`
+    fn rebuild_rank_dir_from(&mut self, chunk_i: usize) {
+        let Some(mut dir) = self.rank_dir.take() else {
+            return;
+        };
+
+        let num_superblocks = self.chunks.len().div_ceil(WORDS_PER_SUPERBLOCK);
+        let first = if dir.len() == num_superblocks + 1 {
+            (chunk_i / WORDS_PER_SUPERBLOCK).min(num_superblocks)
+        } else {
+            dir = vec![0];
+            0
+        };
+        dir.truncate(first + 1);
+        for superblock in first..num_superblocks {
+            let start = superblock * WORDS_PER_SUPERBLOCK;
+            let end = (start + WORDS_PER_SUPERBLOCK).min(self.chunks.len());
+            let active: usize = (start..end)
+                .map(|i| self.active_word(i).count_ones() as usize)
+                .sum();
+            dir.push(dir[superblock] + active);
+        }
+        self.rank_dir = Some(dir);
+    }
+
+    // calls `f(chunk_idx, mask)` for every chunk touched by `range`, where
+    // `mask` selects the bits of that chunk inside the range
+    fn for_each_range_chunk(range: Range<usize>, mut f: impl FnMut(usize, u64)) {
+        if range.start >= range.end {
+            return;
+        }
+        let (first_chunk, first_bit) = Self::bit_pos(range.start);
+        let (last_chunk, last_bit) = Self::bit_pos(range.end - 1);
+        for chunk_i in first_chunk..=last_chunk {
+            let lo = if chunk_i == first_chunk { first_bit } else { 0 };
+            let hi = if chunk_i == last_chunk { last_bit } else { 63 };
+            f(chunk_i, (u64::MAX << lo) & (u64::MAX >> (63 - hi)));
+        }
+    }
+
     pub fn iter(&self) -> BitmaskSkipfieldIter<'_> {
-        BitmaskSkipfieldIter::new(&self.chunks, self.len)
+        BitmaskSkipfieldIter::with_summary(&self.chunks, self.len, &self.summary)
+    }
+}
+
+// bits of a chunk that lie inside the field
+#[inline]
+fn valid_bits(len: usize, chunk_i: usize) -> u64 {
+    let valid = len - chunk_i * 64;
+    if valid < 64 {
+        (1u64 << valid) - 1
+    } else {
+        u64::MAX
     }
 }
 
+// active bits of a chunk, with the padding past `len` masked off
+#[inline]
+fn active_bits(chunks: &[u64], len: usize, chunk_i: usize) -> u64 {
+    !chunks[chunk_i] & valid_bits(len, chunk_i)
+}
+
 pub struct BitmaskSkipfieldIter<'a> {
     chunks: &'a [u64],
     len: usize,
-    chunk_i: usize,
-    bitset: u64,
+    summary: Option<&'a ChunkSummary>,
+    front_chunk: usize,
+    front_bits: u64,
+    back_chunk: usize,
+    back_bits: u64,
+    // front and back each see every remaining index, so stopping after
+    // `remaining` yields keeps them from handing out the same index twice
+    remaining: usize,
 }
 
 impl<'a> BitmaskSkipfieldIter<'a> {
@@ -105,24 +572,47 @@ impl<'a> BitmaskSkipfieldIter<'a> {
         let mut iter = Self {
             chunks,
             len,
-            chunk_i: 0,
-            bitset: 0,
+            summary: None,
+            front_chunk: 0,
+            front_bits: 0,
+            back_chunk: 0,
+            back_bits: 0,
+            remaining: 0,
         };
         if !chunks.is_empty() {
-            iter.bitset = !chunks[0];
+            iter.back_chunk = chunks.len() - 1;
+            iter.front_bits = active_bits(chunks, len, 0);
+            iter.back_bits = active_bits(chunks, len, iter.back_chunk);
+            iter.remaining = (0..chunks.len())
+                .map(|i| active_bits(chunks, len, i).count_ones() as usize)
+                .sum();
         }
         iter
     }
 
-    fn advance_to_next_chunk(&mut self) {
-        while self.chunk_i < self.chunks.len() {
-            let inv = !self.chunks[self.chunk_i];
-            if inv != 0 {
-                self.bitset = inv;
-                return;
-            }
-            self.chunk_i += 1;
+    // jumps over chunks without active slots, and only popcounts the rest
+    pub(crate) fn with_summary(chunks: &'a [u64], len: usize, summary: &'a ChunkSummary) -> Self {
+        let mut iter = Self {
+            chunks,
+            len,
+            summary: Some(summary),
+            front_chunk: 0,
+            front_bits: 0,
+            back_chunk: 0,
+            back_bits: 0,
+            remaining: 0,
+        };
+        if let (Some(front), Some(back)) = (summary.next_from(0), summary.prev_before(chunks.len())) {
+            iter.front_chunk = front;
+            iter.back_chunk = back;
+            iter.front_bits = active_bits(chunks, len, front);
+            iter.back_bits = active_bits(chunks, len, back);
+            iter.remaining = summary
+                .chunks()
+                .map(|i| active_bits(chunks, len, i).count_ones() as usize)
+                .sum();
         }
+        iter
     }
 }
 
@@ -130,29 +620,197 @@ impl<'a> Iterator for BitmaskSkipfieldIter<'a> {
     type Item = usize;
 
     fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            while self.bitset != 0 {
-                let tz = self.bitset.trailing_zeros() as usize;
-                self.bitset &= self.bitset - 1;
-                return Some(self.chunk_i * 64 + tz);
-            }
-    
-            self.chunk_i += 1;
-            if self.chunk_i >= self.chunks.len() {
-                return None;
-            }
-    
-            let inv = !self.chunks[self.chunk_i];
-            if inv != 0 {
-                self.bitset = inv;
-            }
+        if self.remaining == 0 {
+            return None;
+        }
+        while self.front_bits == 0 {
+            self.front_chunk = match self.summary {
+                Some(summary) => summary.next_from(self.front_chunk + 1).expect("remaining active slot"),
+                None => self.front_chunk + 1,
+            };
+            self.front_bits = active_bits(self.chunks, self.len, self.front_chunk);
         }
+        let tz = self.front_bits.trailing_zeros() as usize;
+        self.front_bits &= self.front_bits - 1;
+        self.remaining -= 1;
+        Some(self.front_chunk * 64 + tz)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> DoubleEndedIterator for BitmaskSkipfieldIter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        while self.back_bits == 0 {
+            self.back_chunk = match self.summary {
+                Some(summary) => summary.prev_before(self.back_chunk).expect("remaining active slot"),
+                None => self.back_chunk - 1,
+            };
+            self.back_bits = active_bits(self.chunks, self.len, self.back_chunk);
+        }
+        let top = 63 - self.back_bits.leading_zeros() as usize;
+        self.back_bits &= !(1u64 << top);
+        self.remaining -= 1;
+        Some(self.back_chunk * 64 + top)
+    }
+}
+
+impl<'a> ExactSizeIterator for BitmaskSkipfieldIter<'a> {}
+
+impl<'a> FusedIterator for BitmaskSkipfieldIter<'a> {}
+
+impl BitOr for &BitmaskSkipfield {
+    type Output = BitmaskSkipfield;
+
+    fn bitor(self, rhs: Self) -> BitmaskSkipfield {
+        self.union(rhs)
+    }
+}
+
+impl BitAnd for &BitmaskSkipfield {
+    type Output = BitmaskSkipfield;
+
+    fn bitand(self, rhs: Self) -> BitmaskSkipfield {
+        self.intersect(rhs)
+    }
+}
+
+impl Sub for &BitmaskSkipfield {
+    type Output = BitmaskSkipfield;
+
+    fn sub(self, rhs: Self) -> BitmaskSkipfield {
+        self.difference(rhs)
+    }
+}
+
+impl BitXor for &BitmaskSkipfield {
+    type Output = BitmaskSkipfield;
+
+    fn bitxor(self, rhs: Self) -> BitmaskSkipfield {
+        self.symmetric_difference(rhs)
+    }
+}
+
+impl Not for &BitmaskSkipfield {
+    type Output = BitmaskSkipfield;
+
+    fn not(self) -> BitmaskSkipfield {
+        self.complement()
+    }
+}
+
+impl Not for BitmaskSkipfield {
+    type Output = BitmaskSkipfield;
+
+    fn not(mut self) -> BitmaskSkipfield {
+        self.invert();
+        self
+    }
+}
+
+impl BitOrAssign<&BitmaskSkipfield> for BitmaskSkipfield {
+    fn bitor_assign(&mut self, rhs: &BitmaskSkipfield) {
+        self.union_with(rhs);
+    }
+}
+
+impl BitAndAssign<&BitmaskSkipfield> for BitmaskSkipfield {
+    fn bitand_assign(&mut self, rhs: &BitmaskSkipfield) {
+        self.intersect_with(rhs);
+    }
+}
+
+impl SubAssign<&BitmaskSkipfield> for BitmaskSkipfield {
+    fn sub_assign(&mut self, rhs: &BitmaskSkipfield) {
+        self.difference_with(rhs);
+    }
+}
+
+impl BitXorAssign<&BitmaskSkipfield> for BitmaskSkipfield {
+    fn bitxor_assign(&mut self, rhs: &BitmaskSkipfield) {
+        self.symmetric_difference_with(rhs);
+    }
+}
+
+impl Skipfield for BitmaskSkipfield {
+    fn new(len: usize) -> Self {
+        BitmaskSkipfield::new(len)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn skip(&mut self, idx: usize) {
+        BitmaskSkipfield::skip(self, idx)
+    }
+
+    fn unskip(&mut self, idx: usize) {
+        BitmaskSkipfield::unskip(self, idx)
+    }
+
+    fn is_skipped(&self, idx: usize) -> bool {
+        BitmaskSkipfield::is_skipped(self, idx)
+    }
+
+    fn first_active(&self) -> Option<usize> {
+        BitmaskSkipfield::first_active(self)
+    }
+
+    fn first_skipped(&self) -> Option<usize> {
+        BitmaskSkipfield::first_skipped(self)
+    }
+
+    fn count_skipped(&self) -> usize {
+        BitmaskSkipfield::count_skipped(self)
+    }
+
+    fn skip_range(&mut self, range: Range<usize>) {
+        BitmaskSkipfield::skip_range(self, range)
+    }
+
+    fn unskip_range(&mut self, range: Range<usize>) {
+        BitmaskSkipfield::unskip_range(self, range)
+    }
+
+    fn is_range_skipped(&self, range: Range<usize>) -> bool {
+        BitmaskSkipfield::is_range_skipped(self, range)
+    }
+
+    fn any_active_in(&self, range: Range<usize>) -> bool {
+        BitmaskSkipfield::any_active_in(self, range)
+    }
+
+    fn count_active_in(&self, range: Range<usize>) -> usize {
+        BitmaskSkipfield::count_active_in(self, range)
+    }
+
+    fn active_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.iter()
+    }
+
+    fn skipped_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        BitmaskSkipfield::skipped_indices(self)
+    }
+
+    fn active_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        BitmaskSkipfield::active_runs(self)
+    }
+
+    fn skipped_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        BitmaskSkipfield::skipped_runs(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use skipfield_core::skipfield_conformance_tests;
 
     #[test]
     fn test_skip_and_unskip_behavior() {
@@ -247,8 +905,8 @@ mod tests {
 
     #[test]
     fn test_no_active_indices_beyond_len() {
-        let len = 70;
-        let num_chunks = (len + 63) / 64;
+        let len: usize = 70;
+        let num_chunks = len.div_ceil(64);
         let total_bits = num_chunks * 64;
         let extra_bits_start = len;
         let extra_bits_end = total_bits;
@@ -363,4 +1021,460 @@ mod tests {
         let result = skipfield.iter().next();
         assert_eq!(result, Some(69));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_skipfield_trait() {
+        fn exercise<S: Skipfield>(sf: &mut S) -> Vec<usize> {
+            sf.skip(0);
+            sf.skip(64);
+            sf.skip(69);
+            sf.unskip(0);
+            sf.active_indices().collect()
+        }
+
+        let mut sf = <BitmaskSkipfield as Skipfield>::new(70);
+        let expected: Vec<usize> = (0..69).filter(|&i| i != 64).collect();
+        assert_eq!(exercise(&mut sf), expected);
+        assert_eq!(Skipfield::len(&sf), 70);
+        assert_eq!(Skipfield::count_skipped(&sf), 2);
+        assert_eq!(Skipfield::count_active(&sf), 68);
+    }
+
+    #[test]
+    fn test_skip_and_unskip_range() {
+        let mut sf = BitmaskSkipfield::new(200);
+        sf.skip_range(10..150);
+        assert_eq!(sf.count_skipped(), 140);
+        assert!(!sf.is_skipped(9));
+        assert!(sf.is_skipped(10));
+        assert!(sf.is_skipped(149));
+        assert!(!sf.is_skipped(150));
+
+        sf.unskip_range(60..70);
+        assert_eq!(sf.count_skipped(), 130);
+        assert!(sf.is_skipped(59));
+        assert!(!sf.is_skipped(60));
+        assert!(!sf.is_skipped(69));
+        assert!(sf.is_skipped(70));
+
+        sf.skip_range(5..5);
+        assert_eq!(sf.count_skipped(), 130);
+    }
+
+    #[test]
+    fn test_range_queries() {
+        let mut sf = BitmaskSkipfield::new(130);
+        sf.skip_range(0..128);
+        sf.unskip(64);
+
+        assert!(sf.is_range_skipped(0..64));
+        assert!(!sf.is_range_skipped(0..65));
+        assert!(sf.any_active_in(64..65));
+        assert!(!sf.any_active_in(65..128));
+        assert!(sf.is_range_skipped(3..3));
+        assert_eq!(sf.count_active_in(0..130), 3);
+        assert_eq!(sf.count_active_in(60..129), 2);
+        assert_eq!(sf.count_active_in(0..0), 0);
+    }
+
+    #[test]
+    fn test_unskip_range_keeps_tail_bits() {
+        let mut sf = BitmaskSkipfield::new(70);
+        sf.skip_range(0..70);
+        assert_eq!(sf.first_active(), None);
+        sf.unskip_range(0..70);
+        assert_eq!(sf.count_active(), 70);
+        assert_eq!(sf.iter().count(), 70);
+    }
+
+    #[test]
+    fn test_next_and_prev_active() {
+        let mut sf = BitmaskSkipfield::new(200);
+        sf.skip_range(0..200);
+        for i in [5, 63, 64, 130, 199] {
+            sf.unskip(i);
+        }
+
+        assert_eq!(sf.next_active_after(0), Some(5));
+        assert_eq!(sf.next_active_after(5), Some(63));
+        assert_eq!(sf.next_active_after(63), Some(64));
+        assert_eq!(sf.next_active_after(64), Some(130));
+        assert_eq!(sf.next_active_after(130), Some(199));
+        assert_eq!(sf.next_active_after(199), None);
+        assert_eq!(sf.next_active_after(500), None);
+        assert_eq!(sf.next_active_after(usize::MAX), None);
+
+        assert_eq!(sf.prev_active_before(199), Some(130));
+        assert_eq!(sf.prev_active_before(130), Some(64));
+        assert_eq!(sf.prev_active_before(64), Some(63));
+        assert_eq!(sf.prev_active_before(5), None);
+        assert_eq!(sf.prev_active_before(0), None);
+        assert_eq!(sf.prev_active_before(500), Some(199));
+    }
+
+    #[test]
+    fn test_last_active_ignores_tail_bits() {
+        let mut sf = BitmaskSkipfield::new(70);
+        assert_eq!(sf.last_active(), Some(69));
+        sf.skip_range(66..70);
+        assert_eq!(sf.last_active(), Some(65));
+        sf.skip_range(0..70);
+        assert_eq!(sf.last_active(), None);
+        assert_eq!(BitmaskSkipfield::new(0).last_active(), None);
+    }
+
+    #[test]
+    fn test_nearest_active() {
+        let mut sf = BitmaskSkipfield::new(100);
+        sf.skip_range(0..100);
+        assert_eq!(sf.nearest_active(50), None);
+
+        sf.unskip(10);
+        sf.unskip(20);
+        assert_eq!(sf.nearest_active(10), Some(10));
+        assert_eq!(sf.nearest_active(14), Some(10));
+        assert_eq!(sf.nearest_active(15), Some(10));
+        assert_eq!(sf.nearest_active(16), Some(20));
+        assert_eq!(sf.nearest_active(0), Some(10));
+        assert_eq!(sf.nearest_active(99), Some(20));
+    }
+
+    #[test]
+    fn test_iter_rev() {
+        let mut sf = BitmaskSkipfield::new(130);
+        sf.skip_range(0..130);
+        for i in [0, 63, 64, 100, 129] {
+            sf.unskip(i);
+        }
+
+        let rev: Vec<_> = sf.iter().rev().collect();
+        assert_eq!(rev, vec![129, 100, 64, 63, 0]);
+        assert_eq!(BitmaskSkipfield::new(70).iter().next_back(), Some(69));
+        assert_eq!(BitmaskSkipfield::new(0).iter().next_back(), None);
+    }
+
+    #[test]
+    fn test_iter_meets_in_the_middle() {
+        let mut sf = BitmaskSkipfield::new(10);
+        sf.skip(4);
+
+        let mut iter = sf.iter();
+        assert_eq!(iter.len(), 9);
+        assert_eq!(iter.next(), Some(0));
+        assert_eq!(iter.next_back(), Some(9));
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.len(), 6);
+
+        let mut rest = Vec::new();
+        while let Some(i) = iter.next_back() {
+            rest.push(i);
+            if let Some(j) = iter.next() {
+                rest.push(j);
+            }
+        }
+        rest.sort();
+        assert_eq!(rest, vec![2, 3, 5, 6, 7, 8]);
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_iter_exact_size() {
+        let mut sf = BitmaskSkipfield::new(1000);
+        sf.skip_range(100..900);
+        let iter = sf.iter();
+        assert_eq!(iter.len(), 200);
+        assert_eq!(iter.size_hint(), (200, Some(200)));
+        assert_eq!(sf.iter().collect::<Vec<_>>().len(), sf.count_active());
+    }
+
+    #[test]
+    fn test_rank_and_select_without_directory() {
+        let mut sf = BitmaskSkipfield::new(100);
+        sf.skip_range(0..10);
+        sf.skip(50);
+
+        assert_eq!(sf.rank(0), 0);
+        assert_eq!(sf.rank(10), 0);
+        assert_eq!(sf.rank(11), 1);
+        assert_eq!(sf.rank(51), 40);
+        assert_eq!(sf.rank(100), 89);
+        assert_eq!(sf.select(0), Some(10));
+        assert_eq!(sf.select(40), Some(51));
+        assert_eq!(sf.select(88), Some(99));
+        assert_eq!(sf.select(89), None);
+    }
+
+    #[test]
+    fn test_rank_directory_matches_scan() {
+        let mut sf = BitmaskSkipfield::new(3000);
+        for i in (0..3000).filter(|i| i % 3 == 0 || i % 7 == 0) {
+            sf.skip(i);
+        }
+        sf.build_rank_directory();
+        assert!(sf.has_rank_directory());
+
+        let active: Vec<usize> = sf.iter().collect();
+        for (k, &idx) in active.iter().enumerate() {
+            assert_eq!(sf.rank(idx), k);
+            assert_eq!(sf.select(k), Some(idx));
+        }
+        assert_eq!(sf.rank(3000), active.len());
+        assert_eq!(sf.select(active.len()), None);
+    }
+
+    #[test]
+    fn test_rank_directory_stays_in_sync() {
+        let mut sf = BitmaskSkipfield::new(2000);
+        sf.build_rank_directory();
+
+        sf.skip(5);
+        sf.skip(5);
+        sf.skip(1500);
+        sf.unskip(1500);
+        sf.unskip(1500);
+        sf.skip_range(600..1200);
+        sf.unskip_range(700..710);
+        sf.skip(1999);
+
+        let active: Vec<usize> = sf.iter().collect();
+        assert_eq!(sf.rank(2000), active.len());
+        for (k, &idx) in active.iter().enumerate().step_by(37) {
+            assert_eq!(sf.rank(idx), k);
+            assert_eq!(sf.select(k), Some(idx));
+        }
+
+        // growing, shrinking and whole-field changes
+        sf.resize(2600, false);
+        sf.truncate(1100);
+        sf.resize(1700, true);
+        sf.invert();
+        let active: Vec<usize> = sf.iter().collect();
+        assert_eq!(sf.rank(1700), active.len());
+        for (k, &idx) in active.iter().enumerate().step_by(23) {
+            assert_eq!(sf.rank(idx), k);
+            assert_eq!(sf.select(k), Some(idx));
+        }
+        assert_eq!(sf.select(active.len()), None);
+
+        sf.drop_rank_directory();
+        assert_eq!(sf.rank(1700), active.len());
+    }
+
+    #[test]
+    fn test_resize_clears_padding() {
+        let mut sf = BitmaskSkipfield::new(70);
+        sf.skip(69);
+        sf.resize(200, false);
+
+        assert_eq!(sf.count_active(), 199);
+        assert!(sf.is_skipped(69));
+        assert!(!sf.is_skipped(70));
+        assert!(!sf.is_skipped(127));
+        assert!(!sf.is_skipped(199));
+        assert_eq!(sf.last_active(), Some(199));
+        assert_eq!(sf.iter().count(), 199);
+
+        sf.resize(300, true);
+        assert_eq!(sf.count_skipped(), 101);
+        assert_eq!(sf.last_active(), Some(199));
+    }
+
+    #[test]
+    fn test_push_and_truncate() {
+        let mut sf = BitmaskSkipfield::new(0);
+        for i in 0..130 {
+            sf.push(i % 2 == 0);
+        }
+        assert_eq!(Skipfield::len(&sf), 130);
+        assert_eq!(sf.count_skipped(), 65);
+        assert_eq!(sf.first_active(), Some(1));
+
+        sf.truncate(65);
+        assert_eq!(Skipfield::len(&sf), 65);
+        assert_eq!(sf.count_skipped(), 33);
+        assert_eq!(sf.iter().next_back(), Some(63));
+
+        sf.resize(66, false);
+        assert_eq!(sf.last_active(), Some(65));
+    }
+
+    #[test]
+    fn test_trim_trailing_skipped() {
+        let mut sf = BitmaskSkipfield::new(300);
+        sf.skip_range(130..300);
+        sf.build_rank_directory();
+        sf.trim_trailing_skipped();
+        assert_eq!(Skipfield::len(&sf), 130);
+        assert_eq!(sf.rank(130), 130);
+
+        sf.skip_range(0..130);
+        sf.trim_trailing_skipped();
+        assert_eq!(Skipfield::len(&sf), 0);
+
+        sf.reserve(1000);
+        sf.resize(10, false);
+        sf.shrink_to_fit();
+        assert_eq!(sf.count_active(), 10);
+    }
+
+    #[test]
+    fn test_skipped_indices() {
+        let mut sf = BitmaskSkipfield::new(130);
+        for i in [0, 63, 64, 65, 129] {
+            sf.skip(i);
+        }
+        assert_eq!(sf.skipped_indices().collect::<Vec<_>>(), vec![0, 63, 64, 65, 129]);
+        assert_eq!(BitmaskSkipfield::new(70).skipped_indices().count(), 0);
+    }
+
+    #[test]
+    fn test_runs() {
+        let mut sf = BitmaskSkipfield::new(200);
+        sf.skip_range(0..3);
+        sf.skip_range(60..130);
+        sf.skip(199);
+
+        assert_eq!(sf.skipped_runs().collect::<Vec<_>>(), vec![(0, 3), (60, 70), (199, 1)]);
+        assert_eq!(sf.active_runs().collect::<Vec<_>>(), vec![(3, 57), (130, 69)]);
+
+        let full = BitmaskSkipfield::new(70);
+        assert_eq!(full.active_runs().collect::<Vec<_>>(), vec![(0, 70)]);
+        assert_eq!(full.skipped_runs().count(), 0);
+        assert_eq!(BitmaskSkipfield::new(0).active_runs().count(), 0);
+    }
+
+    fn field_with(len: usize, skipped: &[usize]) -> BitmaskSkipfield {
+        let mut sf = BitmaskSkipfield::new(len);
+        for &i in skipped {
+            sf.skip(i);
+        }
+        sf
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let a = field_with(70, &[0, 1, 64, 65]);
+        let b = field_with(70, &[1, 2, 65, 69]);
+
+        assert_eq!(a.union(&b).skipped_indices().collect::<Vec<_>>(), vec![0, 1, 2, 64, 65, 69]);
+        assert_eq!(a.intersect(&b).skipped_indices().collect::<Vec<_>>(), vec![1, 65]);
+        assert_eq!(a.difference(&b).skipped_indices().collect::<Vec<_>>(), vec![0, 64]);
+        assert_eq!(a.symmetric_difference(&b).skipped_indices().collect::<Vec<_>>(), vec![0, 2, 64, 69]);
+
+        let not_a = a.complement();
+        assert_eq!(not_a.count_skipped(), 66);
+        assert_eq!(not_a.active_indices_2().collect::<Vec<_>>(), vec![0, 1, 64, 65]);
+    }
+
+    #[test]
+    fn test_set_algebra_keeps_tail_bits() {
+        let a = field_with(70, &[]);
+        let b = field_with(70, &[3]);
+
+        let diff = &a - &b;
+        assert_eq!(diff.count_active(), 70);
+        assert_eq!(diff.iter().count(), 70);
+        assert_eq!(diff.active_indices_2().count(), 70);
+
+        let xor = &a ^ &b;
+        assert_eq!(xor.count_skipped(), 1);
+        assert_eq!(xor.active_indices_2().count(), 69);
+
+        let all = !BitmaskSkipfield::new(70);
+        assert_eq!(all.first_active(), None);
+        assert_eq!(all.active_indices_2().count(), 0);
+        assert_eq!((!&all).count_active(), 70);
+    }
+
+    #[test]
+    fn test_set_operators() {
+        let mut erased = field_with(10, &[1, 5]);
+        let hidden = field_with(10, &[5, 7]);
+
+        assert_eq!((&erased | &hidden).skipped_indices().collect::<Vec<_>>(), vec![1, 5, 7]);
+        assert_eq!((&erased & &hidden).skipped_indices().collect::<Vec<_>>(), vec![5]);
+
+        erased.build_rank_directory();
+        erased |= &hidden;
+        assert_eq!(erased.rank(10), 7);
+        erased -= &hidden;
+        assert_eq!(erased.skipped_indices().collect::<Vec<_>>(), vec![1]);
+        erased ^= &hidden;
+        assert_eq!(erased.skipped_indices().collect::<Vec<_>>(), vec![1, 5, 7]);
+        erased &= &hidden;
+        assert_eq!(erased.skipped_indices().collect::<Vec<_>>(), vec![5, 7]);
+        assert_eq!(erased.rank(10), 8);
+    }
+
+    #[test]
+    #[should_panic(expected = "same length")]
+    fn test_set_algebra_length_mismatch() {
+        BitmaskSkipfield::new(10).union_with(&BitmaskSkipfield::new(11));
+    }
+
+    #[test]
+    fn test_sparse_field_queries_use_summary() {
+        let len = 10_000_000;
+        let mut sf = BitmaskSkipfield::new(len);
+        sf.skip_range(0..len);
+        assert_eq!(sf.first_active(), None);
+        assert_eq!(sf.count_active(), 0);
+        assert_eq!(sf.iter().len(), 0);
+
+        for &i in &[5_000_000, 17, 9_999_999] {
+            sf.unskip(i);
+        }
+        assert_eq!(sf.first_active(), Some(17));
+        assert_eq!(sf.next_active_after(17), Some(5_000_000));
+        assert_eq!(sf.prev_active_before(9_999_999), Some(5_000_000));
+        assert_eq!(sf.last_active(), Some(9_999_999));
+        assert_eq!(sf.count_active(), 3);
+        assert_eq!(sf.count_skipped(), len - 3);
+        assert_eq!(sf.iter().collect::<Vec<_>>(), vec![17, 5_000_000, 9_999_999]);
+        assert_eq!(sf.iter().rev().collect::<Vec<_>>(), vec![9_999_999, 5_000_000, 17]);
+
+        sf.skip(5_000_000);
+        assert_eq!(sf.next_active_after(17), Some(9_999_999));
+        sf.truncate(9_000_000);
+        assert_eq!(sf.last_active(), Some(17));
+        sf.resize(len, false);
+        assert_eq!(sf.next_active_after(17), Some(9_000_000));
+        assert_eq!(sf.count_active(), 1_000_001);
+    }
+
+    #[test]
+    fn test_summary_follows_set_algebra() {
+        let mut a = BitmaskSkipfield::new(200_000);
+        a.skip_range(0..200_000);
+        let mut b = a.clone();
+        b.unskip(150_000);
+
+        a &= &b;
+        assert_eq!(a.first_active(), Some(150_000));
+        a.invert();
+        assert_eq!(a.first_active(), Some(0));
+        assert_eq!(a.count_active(), 199_999);
+        assert_eq!(a.iter().rev().nth(49_999), Some(149_999));
+    }
+
+    #[test]
+    fn test_from_flags() {
+        let flags: Vec<bool> = (0..200).map(|i| i % 3 == 0 || (64..130).contains(&i)).collect();
+        let sf = BitmaskSkipfield::from_flags(&flags);
+        assert_eq!(Skipfield::len(&sf), 200);
+        for (i, &flag) in flags.iter().enumerate() {
+            assert_eq!(sf.is_skipped(i), flag);
+        }
+        assert_eq!(sf.count_skipped(), flags.iter().filter(|&&f| f).count());
+        assert_eq!(sf.iter().count(), sf.count_active());
+        assert_eq!(sf.first_active(), Some(1));
+        assert_eq!(sf.next_active_after(63), Some(130));
+
+        let empty = BitmaskSkipfield::from_flags(&[]);
+        assert_eq!(empty.first_active(), None);
+        assert_eq!(empty.count_skipped(), 0);
+    }
+
+    skipfield_conformance_tests!(BitmaskSkipfield);
+}
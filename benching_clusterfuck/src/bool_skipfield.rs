@@ -1,3 +1,9 @@
+use std::ops::Range;
+
+use skipfield_core::Skipfield;
+
+use crate::simd;
+
 pub struct BoolSkipfield {
     flags: Vec<bool>,
 }
@@ -9,6 +15,41 @@ impl BoolSkipfield {
         }
     }
 
+    /// Builds a field from one flag per slot, `true` meaning skipped.
+    pub fn from_flags(flags: Vec<bool>) -> Self {
+        Self { flags }
+    }
+
+    /// One flag per slot, `true` meaning skipped.
+    pub fn flags(&self) -> &[bool] {
+        &self.flags
+    }
+
+    pub fn push(&mut self, skipped: bool) {
+        self.flags.push(skipped);
+    }
+
+    pub fn resize(&mut self, new_len: usize, skipped: bool) {
+        self.flags.resize(new_len, skipped);
+    }
+
+    pub fn truncate(&mut self, new_len: usize) {
+        self.flags.truncate(new_len);
+    }
+
+    pub fn trim_trailing_skipped(&mut self) {
+        let new_len = self.flags.iter().rposition(|&bit| !bit).map_or(0, |i| i + 1);
+        self.flags.truncate(new_len);
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.flags.reserve(additional);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.flags.shrink_to_fit();
+    }
+
     pub fn skip(&mut self, index: usize) {
         self.flags[index] = true;
     }
@@ -21,14 +62,16 @@ impl BoolSkipfield {
         self.flags[index]
     }
 
+    pub fn skip_range(&mut self, range: Range<usize>) {
+        self.flags[range].fill(true);
+    }
+
+    pub fn unskip_range(&mut self, range: Range<usize>) {
+        self.flags[range].fill(false);
+    }
+
     pub fn count_skipped(&self) -> usize {
-        let mut count = 0;
-        for &bit in &self.flags {
-            if bit {
-                count += 1;
-            }
-        }
-        count
+        simd::count_true(&self.flags)
     }
 
     pub fn count_active(&self) -> usize {
@@ -43,18 +86,60 @@ impl BoolSkipfield {
     }
     
     pub fn first_active(&self) -> Option<usize> {
-        for (i, &bit) in self.flags.iter().enumerate() {
-            if !bit {
-                return Some(i);
-            }
-        }
-        None
+        simd::first_false(&self.flags)
+    }
+}
+
+impl Skipfield for BoolSkipfield {
+    fn new(len: usize) -> Self {
+        BoolSkipfield::new(len)
+    }
+
+    fn len(&self) -> usize {
+        self.flags.len()
+    }
+
+    fn skip(&mut self, idx: usize) {
+        BoolSkipfield::skip(self, idx)
+    }
+
+    fn unskip(&mut self, idx: usize) {
+        BoolSkipfield::unskip(self, idx)
+    }
+
+    fn is_skipped(&self, idx: usize) -> bool {
+        BoolSkipfield::is_skipped(self, idx)
+    }
+
+    fn skip_range(&mut self, range: Range<usize>) {
+        BoolSkipfield::skip_range(self, range)
+    }
+
+    fn unskip_range(&mut self, range: Range<usize>) {
+        BoolSkipfield::unskip_range(self, range)
+    }
+
+    fn count_active_in(&self, range: Range<usize>) -> usize {
+        self.flags[range].iter().filter(|&&bit| !bit).count()
+    }
+
+    fn first_active(&self) -> Option<usize> {
+        BoolSkipfield::first_active(self)
+    }
+
+    fn count_skipped(&self) -> usize {
+        BoolSkipfield::count_skipped(self)
+    }
+
+    fn active_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        BoolSkipfield::active_indices(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use skipfield_core::skipfield_conformance_tests;
 
     #[test]
     fn test_skip_and_unskip() {
@@ -123,4 +208,62 @@ mod tests {
         let indices: Vec<_> = sf.active_indices().collect();
         assert!(indices.is_empty());
     }
+
+    #[test]
+    fn test_skipfield_trait() {
+        fn exercise<S: Skipfield>(sf: &mut S) -> Vec<usize> {
+            sf.skip(0);
+            sf.skip(2);
+            sf.unskip(0);
+            sf.active_indices().collect()
+        }
+
+        let mut sf = <BoolSkipfield as Skipfield>::new(4);
+        assert_eq!(exercise(&mut sf), vec![0, 1, 3]);
+        assert_eq!(Skipfield::len(&sf), 4);
+        assert_eq!(Skipfield::count_skipped(&sf), 1);
+        assert!(Skipfield::is_active(&sf, 1));
+    }
+
+    #[test]
+    fn test_range_operations() {
+        let mut sf = BoolSkipfield::new(10);
+        sf.skip_range(2..8);
+        sf.unskip_range(4..6);
+
+        assert_eq!(sf.active_indices().collect::<Vec<_>>(), vec![0, 1, 4, 5, 8, 9]);
+        assert!(Skipfield::is_range_skipped(&sf, 2..4));
+        assert!(!Skipfield::is_range_skipped(&sf, 2..5));
+        assert!(!Skipfield::any_active_in(&sf, 6..8));
+        assert_eq!(Skipfield::count_active_in(&sf, 0..10), 6);
+        assert_eq!(Skipfield::count_active_in(&sf, 3..9), 3);
+    }
+
+    #[test]
+    fn test_resize_and_trim() {
+        let mut sf = BoolSkipfield::new(3);
+        sf.push(true);
+        sf.resize(8, false);
+        sf.skip_range(6..8);
+        assert_eq!(sf.active_indices().collect::<Vec<_>>(), vec![0, 1, 2, 4, 5]);
+
+        sf.trim_trailing_skipped();
+        assert_eq!(Skipfield::len(&sf), 6);
+        sf.truncate(4);
+        sf.trim_trailing_skipped();
+        assert_eq!(Skipfield::len(&sf), 3);
+    }
+
+    #[test]
+    fn test_skipped_indices_and_runs() {
+        let mut sf = BoolSkipfield::new(8);
+        sf.skip_range(1..3);
+        sf.skip(7);
+
+        assert_eq!(Skipfield::skipped_indices(&sf).collect::<Vec<_>>(), vec![1, 2, 7]);
+        assert_eq!(Skipfield::skipped_runs(&sf).collect::<Vec<_>>(), vec![(1, 2), (7, 1)]);
+        assert_eq!(Skipfield::active_runs(&sf).collect::<Vec<_>>(), vec![(0, 1), (3, 4)]);
+    }
+
+    skipfield_conformance_tests!(BoolSkipfield);
 }
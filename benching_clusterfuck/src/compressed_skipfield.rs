@@ -0,0 +1,635 @@
+use std::ops::Range;
+
+use skipfield_core::Skipfield;
+
+use crate::lcjc_skipfield::active_gaps;
+
+const CONTAINER_SLOTS: usize = 1 << 16;
+const BITMAP_WORDS: usize = CONTAINER_SLOTS / 64;
+// an array of more skipped offsets than this is bigger than a bitmap
+const ARRAY_MAX: usize = 4096;
+// a bitmap only goes back to an array well below `ARRAY_MAX`, so toggling a
+// slot at the threshold doesn't rebuild the container every time
+const BITMAP_MIN: usize = ARRAY_MAX / 2;
+// a list of more runs than this is bigger than a bitmap
+const RUNS_MAX: usize = 2048;
+
+/// How a `CompressedSkipfield` container stores its skipped slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerKind {
+    Array,
+    Bitmap,
+    Runs,
+}
+
+#[derive(Clone)]
+enum Container {
+    // sorted offsets of the skipped slots
+    Array(Vec<u16>),
+    // one bit per slot, set if skipped, and how many are set
+    Bitmap(Box<[u64; BITMAP_WORDS]>, usize),
+    // skipped runs as inclusive `(start, last)`, ascending and never touching
+    Runs(Vec<(u16, u16)>),
+}
+
+impl Container {
+    fn empty() -> Self {
+        Container::Array(Vec::new())
+    }
+
+    fn full(slots: usize) -> Self {
+        Container::Runs(vec![(0, (slots - 1) as u16)])
+    }
+
+    fn kind(&self) -> ContainerKind {
+        match self {
+            Container::Array(_) => ContainerKind::Array,
+            Container::Bitmap(..) => ContainerKind::Bitmap,
+            Container::Runs(_) => ContainerKind::Runs,
+        }
+    }
+
+    fn contains(&self, offset: u16) -> bool {
+        match self {
+            Container::Array(offsets) => offsets.binary_search(&offset).is_ok(),
+            Container::Bitmap(words, _) => words[offset as usize / 64] & (1 << (offset % 64)) != 0,
+            Container::Runs(runs) => {
+                let i = runs.partition_point(|&(start, _)| start <= offset);
+                i > 0 && runs[i - 1].1 >= offset
+            }
+        }
+    }
+
+    fn cardinality(&self) -> usize {
+        match self {
+            Container::Array(offsets) => offsets.len(),
+            Container::Bitmap(_, count) => *count,
+            Container::Runs(runs) => runs.iter().map(|&(start, last)| (last - start) as usize + 1).sum(),
+        }
+    }
+
+    fn insert(&mut self, offset: u16) -> bool {
+        let inserted = match self {
+            Container::Array(offsets) => match offsets.binary_search(&offset) {
+                Ok(_) => false,
+                Err(i) => {
+                    offsets.insert(i, offset);
+                    true
+                }
+            },
+            Container::Bitmap(words, count) => {
+                let (word_i, bit) = (offset as usize / 64, 1u64 << (offset % 64));
+                let was_set = words[word_i] & bit != 0;
+                words[word_i] |= bit;
+                *count += !was_set as usize;
+                !was_set
+            }
+            Container::Runs(runs) => insert_into_runs(runs, offset),
+        };
+        if inserted {
+            self.fit();
+        }
+        inserted
+    }
+
+    fn remove(&mut self, offset: u16) -> bool {
+        let removed = match self {
+            Container::Array(offsets) => match offsets.binary_search(&offset) {
+                Ok(i) => {
+                    offsets.remove(i);
+                    true
+                }
+                Err(_) => false,
+            },
+            Container::Bitmap(words, count) => {
+                let (word_i, bit) = (offset as usize / 64, 1u64 << (offset % 64));
+                let was_set = words[word_i] & bit != 0;
+                words[word_i] &= !bit;
+                *count -= was_set as usize;
+                was_set
+            }
+            Container::Runs(runs) => remove_from_runs(runs, offset),
+        };
+        if removed {
+            self.fit();
+        }
+        removed
+    }
+
+    // cheap checks after a single-slot change: only moves away from a kind
+    // once it has clearly become the bigger one
+    fn fit(&mut self) {
+        let switch = match self {
+            Container::Array(offsets) => offsets.len() > ARRAY_MAX,
+            Container::Bitmap(_, count) => *count <= BITMAP_MIN,
+            Container::Runs(runs) => runs.len() > RUNS_MAX,
+        };
+        if switch {
+            self.optimize();
+        }
+    }
+
+    /// Switches to whichever kind takes the fewest bytes.
+    fn optimize(&mut self) {
+        let runs = self.skipped_runs();
+        let cardinality: usize = runs.iter().map(|&(_, len)| len).sum();
+        let array_bytes = 2 * cardinality;
+        let runs_bytes = 4 * runs.len();
+        let bitmap_bytes = 8 * BITMAP_WORDS;
+
+        *self = if runs_bytes <= array_bytes.min(bitmap_bytes) {
+            Container::Runs(runs.iter().map(|&(start, len)| (start as u16, (start + len - 1) as u16)).collect())
+        } else if array_bytes <= bitmap_bytes {
+            Container::Array(runs.iter().flat_map(|&(start, len)| start..start + len).map(|o| o as u16).collect())
+        } else {
+            let mut words = Box::new([0u64; BITMAP_WORDS]);
+            for &(start, len) in &runs {
+                set_bits(&mut words[..], start..start + len, true);
+            }
+            Container::Bitmap(words, cardinality)
+        };
+    }
+
+    fn to_bitmap(&self) -> Box<[u64; BITMAP_WORDS]> {
+        if let Container::Bitmap(words, _) = self {
+            return words.clone();
+        }
+        let mut words = Box::new([0u64; BITMAP_WORDS]);
+        for (start, len) in self.skipped_runs() {
+            set_bits(&mut words[..], start..start + len, true);
+        }
+        words
+    }
+
+    // sets or clears `range` of a container holding `slots` slots
+    fn set_range(&mut self, range: Range<usize>, slots: usize, skipped: bool) {
+        if range.start >= range.end {
+            return;
+        }
+        if range.start == 0 && range.end == slots {
+            *self = if skipped { Container::full(slots) } else { Container::empty() };
+            return;
+        }
+        let mut words = self.to_bitmap();
+        set_bits(&mut words[..], range, skipped);
+        let count = words.iter().map(|w| w.count_ones() as usize).sum();
+        *self = Container::Bitmap(words, count);
+        self.optimize();
+    }
+
+    /// Skipped runs as `(start, len)` offsets.
+    fn skipped_runs(&self) -> Vec<(usize, usize)> {
+        match self {
+            Container::Array(offsets) => {
+                let mut runs: Vec<(usize, usize)> = Vec::new();
+                for &o in offsets {
+                    match runs.last_mut() {
+                        Some((start, len)) if *start + *len == o as usize => *len += 1,
+                        _ => runs.push((o as usize, 1)),
+                    }
+                }
+                runs
+            }
+            Container::Bitmap(words, _) => {
+                let mut runs = Vec::new();
+                let mut pos = 0;
+                while let Some(start) = next_bit(&words[..], pos, true) {
+                    let end = next_bit(&words[..], start, false).unwrap_or(CONTAINER_SLOTS);
+                    runs.push((start, end - start));
+                    pos = end;
+                }
+                runs
+            }
+            Container::Runs(runs) => runs
+                .iter()
+                .map(|&(start, last)| (start as usize, (last - start) as usize + 1))
+                .collect(),
+        }
+    }
+
+    // first active offset, which may lie past the slots the container uses
+    fn first_active(&self) -> usize {
+        match self {
+            // skipped offsets are distinct and sorted, so `offsets[i] == i`
+            // holds for a prefix and fails from the first gap on
+            Container::Array(offsets) => first_gap(offsets),
+            Container::Bitmap(words, _) => next_bit(&words[..], 0, false).unwrap_or(CONTAINER_SLOTS),
+            Container::Runs(runs) => match runs.first() {
+                Some(&(0, last)) => last as usize + 1,
+                _ => 0,
+            },
+        }
+    }
+}
+
+// length of the prefix where `offsets[i] == i`
+fn first_gap(offsets: &[u16]) -> usize {
+    let mut lo = 0;
+    let mut hi = offsets.len();
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if offsets[mid] as usize == mid {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+fn insert_into_runs(runs: &mut Vec<(u16, u16)>, offset: u16) -> bool {
+    let i = runs.partition_point(|&(start, _)| start <= offset);
+    if i > 0 && runs[i - 1].1 >= offset {
+        return false;
+    }
+    let joins_left = i > 0 && runs[i - 1].1 as u32 + 1 == offset as u32;
+    let joins_right = i < runs.len() && runs[i].0 as u32 == offset as u32 + 1;
+    match (joins_left, joins_right) {
+        (true, true) => {
+            runs[i - 1].1 = runs[i].1;
+            runs.remove(i);
+        }
+        (true, false) => runs[i - 1].1 = offset,
+        (false, true) => runs[i].0 = offset,
+        (false, false) => runs.insert(i, (offset, offset)),
+    }
+    true
+}
+
+fn remove_from_runs(runs: &mut Vec<(u16, u16)>, offset: u16) -> bool {
+    let i = runs.partition_point(|&(start, _)| start <= offset);
+    if i == 0 || runs[i - 1].1 < offset {
+        return false;
+    }
+    let (start, last) = runs[i - 1];
+    if start == last {
+        runs.remove(i - 1);
+    } else if offset == start {
+        runs[i - 1].0 = start + 1;
+    } else if offset == last {
+        runs[i - 1].1 = last - 1;
+    } else {
+        runs[i - 1].1 = offset - 1;
+        runs.insert(i, (offset + 1, last));
+    }
+    true
+}
+
+fn set_bits(words: &mut [u64], range: Range<usize>, value: bool) {
+    if range.start >= range.end {
+        return;
+    }
+    let (first, last) = (range.start / 64, (range.end - 1) / 64);
+    for (word_i, word) in words.iter_mut().enumerate().take(last + 1).skip(first) {
+        let lo = if word_i == first { range.start % 64 } else { 0 };
+        let hi = if word_i == last { (range.end - 1) % 64 } else { 63 };
+        let mask = (u64::MAX << lo) & (u64::MAX >> (63 - hi));
+        if value {
+            *word |= mask;
+        } else {
+            *word &= !mask;
+        }
+    }
+}
+
+// first bit at or after `from` that is set (or clear)
+fn next_bit(words: &[u64], from: usize, set: bool) -> Option<usize> {
+    let mut word_i = from / 64;
+    if word_i >= words.len() {
+        return None;
+    }
+    let word_at = |w: u64| if set { w } else { !w };
+    let mut word = word_at(words[word_i]) & (u64::MAX << (from % 64));
+    loop {
+        if word != 0 {
+            return Some(word_i * 64 + word.trailing_zeros() as usize);
+        }
+        word_i += 1;
+        if word_i == words.len() {
+            return None;
+        }
+        word = word_at(words[word_i]);
+    }
+}
+
+/// Roaring-style skipfield for huge, very sparse or very dense fields.
+///
+/// The index space is split into containers of 65,536 slots. Each container
+/// stores its skipped slots as a sorted array of offsets, a bitmap, or a list
+/// of runs, and switches to the smallest of the three as it changes.
+#[derive(Clone)]
+pub struct CompressedSkipfield {
+    containers: Vec<Container>,
+    len: usize,
+}
+
+impl CompressedSkipfield {
+    pub fn new(len: usize) -> Self {
+        Self {
+            containers: vec![Container::empty(); len.div_ceil(CONTAINER_SLOTS)],
+            len,
+        }
+    }
+
+    pub fn skip(&mut self, idx: usize) {
+        let (container_i, offset) = self.locate(idx);
+        self.containers[container_i].insert(offset);
+    }
+
+    pub fn unskip(&mut self, idx: usize) {
+        let (container_i, offset) = self.locate(idx);
+        self.containers[container_i].remove(offset);
+    }
+
+    pub fn is_skipped(&self, idx: usize) -> bool {
+        let (container_i, offset) = self.locate(idx);
+        self.containers[container_i].contains(offset)
+    }
+
+    pub fn skip_range(&mut self, range: Range<usize>) {
+        self.set_range(range, true);
+    }
+
+    pub fn unskip_range(&mut self, range: Range<usize>) {
+        self.set_range(range, false);
+    }
+
+    fn set_range(&mut self, range: Range<usize>, skipped: bool) {
+        assert!(range.end <= self.len, "range end {} out of bounds (len = {})", range.end, self.len);
+        if range.start >= range.end {
+            return;
+        }
+        let first = range.start / CONTAINER_SLOTS;
+        let last = (range.end - 1) / CONTAINER_SLOTS;
+        for container_i in first..=last {
+            let base = container_i * CONTAINER_SLOTS;
+            let lo = range.start.max(base) - base;
+            let hi = range.end.min(base + CONTAINER_SLOTS) - base;
+            let slots = self.slots_in(container_i);
+            self.containers[container_i].set_range(lo..hi, slots, skipped);
+        }
+    }
+
+    pub fn first_active(&self) -> Option<usize> {
+        self.containers.iter().enumerate().find_map(|(container_i, container)| {
+            let offset = container.first_active();
+            (offset < self.slots_in(container_i)).then_some(container_i * CONTAINER_SLOTS + offset)
+        })
+    }
+
+    pub fn count_skipped(&self) -> usize {
+        self.containers.iter().map(Container::cardinality).sum()
+    }
+
+    pub fn count_active(&self) -> usize {
+        self.len - self.count_skipped()
+    }
+
+    pub fn active_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.active_runs().flat_map(|(start, len)| start..start + len)
+    }
+
+    pub fn skipped_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.skipped_runs().flat_map(|(start, len)| start..start + len)
+    }
+
+    /// Maximal runs of active slots as `(start, len)`.
+    pub fn active_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        active_gaps(self.skipped_runs(), self.len)
+    }
+
+    /// Maximal runs of skipped slots as `(start, len)`. Runs that meet at a
+    /// container boundary come out joined.
+    pub fn skipped_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let mut runs = self
+            .containers
+            .iter()
+            .enumerate()
+            .flat_map(|(container_i, container)| {
+                let base = container_i * CONTAINER_SLOTS;
+                container.skipped_runs().into_iter().map(move |(start, len)| (base + start, len))
+            })
+            .peekable();
+        std::iter::from_fn(move || {
+            let (start, mut len) = runs.next()?;
+            while let Some((_, next_len)) = runs.next_if(|&(next_start, _)| next_start == start + len) {
+                len += next_len;
+            }
+            Some((start, len))
+        })
+    }
+
+    /// Switches every container to its smallest kind. Single-slot changes only
+    /// switch once a kind has clearly outgrown another, so this can shrink a
+    /// field further after many of them, mostly by finding run containers.
+    pub fn optimize(&mut self) {
+        for container in &mut self.containers {
+            container.optimize();
+        }
+    }
+
+    pub fn container_kinds(&self) -> impl Iterator<Item = ContainerKind> + '_ {
+        self.containers.iter().map(Container::kind)
+    }
+
+    #[inline]
+    fn locate(&self, idx: usize) -> (usize, u16) {
+        assert!(idx < self.len, "index {idx} out of bounds (len = {})", self.len);
+        (idx / CONTAINER_SLOTS, (idx % CONTAINER_SLOTS) as u16)
+    }
+
+    fn slots_in(&self, container_i: usize) -> usize {
+        (self.len - container_i * CONTAINER_SLOTS).min(CONTAINER_SLOTS)
+    }
+}
+
+impl Skipfield for CompressedSkipfield {
+    fn new(len: usize) -> Self {
+        CompressedSkipfield::new(len)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn skip(&mut self, idx: usize) {
+        CompressedSkipfield::skip(self, idx)
+    }
+
+    fn unskip(&mut self, idx: usize) {
+        CompressedSkipfield::unskip(self, idx)
+    }
+
+    fn is_skipped(&self, idx: usize) -> bool {
+        CompressedSkipfield::is_skipped(self, idx)
+    }
+
+    fn first_active(&self) -> Option<usize> {
+        CompressedSkipfield::first_active(self)
+    }
+
+    fn count_skipped(&self) -> usize {
+        CompressedSkipfield::count_skipped(self)
+    }
+
+    fn skip_range(&mut self, range: Range<usize>) {
+        CompressedSkipfield::skip_range(self, range)
+    }
+
+    fn unskip_range(&mut self, range: Range<usize>) {
+        CompressedSkipfield::unskip_range(self, range)
+    }
+
+    fn active_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        CompressedSkipfield::active_indices(self)
+    }
+
+    fn skipped_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        CompressedSkipfield::skipped_indices(self)
+    }
+
+    fn active_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        CompressedSkipfield::active_runs(self)
+    }
+
+    fn skipped_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        CompressedSkipfield::skipped_runs(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use skipfield_core::skipfield_conformance_tests;
+    use crate::bitmask_skipfield::BitmaskSkipfield;
+
+    #[test]
+    fn test_container_kinds_switch() {
+        let mut sf = CompressedSkipfield::new(3 * CONTAINER_SLOTS);
+        assert!(sf.container_kinds().all(|k| k == ContainerKind::Array));
+
+        for i in (0..CONTAINER_SLOTS).step_by(8) {
+            sf.skip(i);
+        }
+        assert_eq!(sf.container_kinds().next(), Some(ContainerKind::Bitmap));
+        for i in (0..CONTAINER_SLOTS).step_by(8).skip(10) {
+            sf.unskip(i);
+        }
+        assert_eq!(sf.container_kinds().next(), Some(ContainerKind::Array));
+        assert_eq!(sf.count_skipped(), 10);
+
+        sf.skip_range(CONTAINER_SLOTS + 5..2 * CONTAINER_SLOTS + 100);
+        assert_eq!(sf.container_kinds().collect::<Vec<_>>()[1..], [ContainerKind::Runs, ContainerKind::Runs]);
+        assert_eq!(sf.skipped_runs().nth(10), Some((CONTAINER_SLOTS + 5, CONTAINER_SLOTS + 95)));
+
+        // punching many holes into a run makes a bitmap the smaller choice
+        for i in (CONTAINER_SLOTS + 10..2 * CONTAINER_SLOTS).step_by(4) {
+            sf.unskip(i);
+        }
+        assert_eq!(sf.container_kinds().nth(1), Some(ContainerKind::Bitmap));
+    }
+
+    #[test]
+    fn test_bitmap_keeps_its_kind_near_the_threshold() {
+        let mut sf = CompressedSkipfield::new(CONTAINER_SLOTS);
+        let offsets: Vec<usize> = (0..CONTAINER_SLOTS).step_by(8).take(ARRAY_MAX + 1).collect();
+        for &i in &offsets {
+            sf.skip(i);
+        }
+        assert_eq!(sf.container_kinds().next(), Some(ContainerKind::Bitmap));
+
+        // flapping around `ARRAY_MAX` stays a bitmap
+        for _ in 0..3 {
+            sf.unskip(offsets[0]);
+            sf.unskip(offsets[1]);
+            assert_eq!(sf.container_kinds().next(), Some(ContainerKind::Bitmap));
+            sf.skip(offsets[0]);
+            sf.skip(offsets[1]);
+        }
+
+        for &i in &offsets[..ARRAY_MAX + 1 - BITMAP_MIN] {
+            sf.unskip(i);
+        }
+        assert_eq!(sf.container_kinds().next(), Some(ContainerKind::Array));
+        assert_eq!(sf.count_skipped(), BITMAP_MIN);
+    }
+
+    #[test]
+    fn test_matches_bitmask() {
+        let len = 2 * CONTAINER_SLOTS + 1234;
+        let mut compressed = CompressedSkipfield::new(len);
+        let mut bitmask = BitmaskSkipfield::new(len);
+
+        let mut x = 0x2545_f491_4f6c_dd1du64;
+        for step in 0..20_000 {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            let i = x as usize % len;
+            match step % 5 {
+                0 | 1 => {
+                    compressed.skip(i);
+                    bitmask.skip(i);
+                }
+                2 => {
+                    compressed.unskip(i);
+                    bitmask.unskip(i);
+                }
+                3 => {
+                    let end = (i + (x >> 40) as usize % 3000).min(len);
+                    compressed.skip_range(i..end);
+                    bitmask.skip_range(i..end);
+                }
+                _ => {
+                    let end = (i + (x >> 40) as usize % 500).min(len);
+                    compressed.unskip_range(i..end);
+                    bitmask.unskip_range(i..end);
+                }
+            }
+        }
+
+        assert_eq!(compressed.count_skipped(), bitmask.count_skipped());
+        assert_eq!(compressed.first_active(), bitmask.first_active());
+        assert!(compressed.active_indices().eq(bitmask.iter()));
+        assert!(compressed.skipped_runs().eq(bitmask.skipped_runs()));
+        for i in (0..len).step_by(97) {
+            assert_eq!(compressed.is_skipped(i), bitmask.is_skipped(i));
+        }
+
+        compressed.optimize();
+        assert!(compressed.active_runs().eq(bitmask.active_runs()));
+    }
+
+    #[test]
+    fn test_first_active_and_tail_container() {
+        let len = CONTAINER_SLOTS + 10;
+        let mut sf = CompressedSkipfield::new(len);
+        sf.skip_range(0..len - 1);
+        assert_eq!(sf.first_active(), Some(len - 1));
+        sf.skip(len - 1);
+        assert_eq!(sf.first_active(), None);
+        assert_eq!(sf.count_active(), 0);
+        assert_eq!(sf.skipped_runs().collect::<Vec<_>>(), vec![(0, len)]);
+
+        sf.unskip_range(3..6);
+        assert_eq!(sf.first_active(), Some(3));
+        sf.unskip(0);
+        assert_eq!(sf.first_active(), Some(0));
+
+        let empty = CompressedSkipfield::new(0);
+        assert_eq!(empty.first_active(), None);
+        assert_eq!(empty.active_indices().count(), 0);
+    }
+
+    #[test]
+    fn test_skipfield_trait() {
+        let mut sf = <CompressedSkipfield as Skipfield>::new(100);
+        Skipfield::skip_range(&mut sf, 10..90);
+        Skipfield::unskip(&mut sf, 50);
+        assert_eq!(Skipfield::count_active(&sf), 21);
+        assert_eq!(Skipfield::active_runs(&sf).collect::<Vec<_>>(), vec![(0, 10), (50, 1), (90, 10)]);
+        assert!(Skipfield::is_range_skipped(&sf, 10..50));
+    }
+
+    skipfield_conformance_tests!(CompressedSkipfield);
+}
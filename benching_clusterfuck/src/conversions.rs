@@ -0,0 +1,186 @@
+//! Bulk conversions between skipfield representations.
+//!
+//! Every conversion is O(n) and works a word or a run at a time rather than
+//! reading and writing one slot at a time. LCJC is built from the skipped
+//! runs of the source in a single scan, which fails if a run doesn't fit the
+//! node type; the other directions can't fail.
+//!
+//! Conversions to the skipfields of the other crates are behind the feature
+//! named after each crate.
+
+use std::fmt;
+
+use skipfield_core::Skipfield;
+
+use crate::bitmask_skipfield::BitmaskSkipfield;
+use crate::bool_skipfield::BoolSkipfield;
+use crate::lcjc_skipfield::{LCJCNode, LCJCSkipfield};
+
+#[cfg(feature = "lockless")]
+mod lockless;
+#[cfg(feature = "optimized_seq")]
+mod optimized_seq;
+
+/// A skipped run is too long for a non-chained LCJC field of the target node
+/// type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunTooLong {
+    pub start: usize,
+    pub len: usize,
+    pub max: usize,
+}
+
+impl fmt::Display for RunTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "skipped run of {} slots at {} doesn't fit a node of max {}",
+            self.len, self.start, self.max
+        )
+    }
+}
+
+impl std::error::Error for RunTooLong {}
+
+impl From<&BoolSkipfield> for BitmaskSkipfield {
+    fn from(sf: &BoolSkipfield) -> Self {
+        BitmaskSkipfield::from_flags(sf.flags())
+    }
+}
+
+impl From<&BitmaskSkipfield> for BoolSkipfield {
+    fn from(sf: &BitmaskSkipfield) -> Self {
+        let len = sf.len();
+        let flags = sf
+            .words()
+            .iter()
+            .flat_map(|&word| (0..64).map(move |bit| word & (1 << bit) != 0))
+            .take(len)
+            .collect();
+        BoolSkipfield::from_flags(flags)
+    }
+}
+
+impl<N: LCJCNode> TryFrom<&BitmaskSkipfield> for LCJCSkipfield<N> {
+    type Error = RunTooLong;
+
+    fn try_from(sf: &BitmaskSkipfield) -> Result<Self, RunTooLong> {
+        LCJCSkipfield::from_runs(sf.len(), sf.skipped_runs(), false)
+    }
+}
+
+impl<N: LCJCNode> From<&LCJCSkipfield<N>> for BitmaskSkipfield {
+    fn from(sf: &LCJCSkipfield<N>) -> Self {
+        let len = sf.len();
+        let mut words = vec![0u64; len.div_ceil(64)];
+        for (start, run_len) in sf.skipped_runs() {
+            set_bits(&mut words, start, start + run_len);
+        }
+        BitmaskSkipfield::from_words(words, len)
+    }
+}
+
+impl<N: LCJCNode> TryFrom<&BoolSkipfield> for LCJCSkipfield<N> {
+    type Error = RunTooLong;
+
+    // packing first lets the run scan skip a word at a time
+    fn try_from(sf: &BoolSkipfield) -> Result<Self, RunTooLong> {
+        LCJCSkipfield::try_from(&BitmaskSkipfield::from(sf))
+    }
+}
+
+impl<N: LCJCNode> From<&LCJCSkipfield<N>> for BoolSkipfield {
+    fn from(sf: &LCJCSkipfield<N>) -> Self {
+        let mut flags = vec![false; sf.len()];
+        for (start, run_len) in sf.skipped_runs() {
+            flags[start..start + run_len].fill(true);
+        }
+        BoolSkipfield::from_flags(flags)
+    }
+}
+
+// sets bits `start..end`, filling whole words in between
+fn set_bits(words: &mut [u64], start: usize, end: usize) {
+    let (first, last) = (start / 64, (end - 1) / 64);
+    let lo = u64::MAX << (start % 64);
+    let hi = u64::MAX >> (63 - (end - 1) % 64);
+    if first == last {
+        words[first] |= lo & hi;
+        return;
+    }
+    words[first] |= lo;
+    words[first + 1..last].fill(u64::MAX);
+    words[last] |= hi;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(len: usize) -> Vec<bool> {
+        // runs of every length from 1 up, across word boundaries
+        let mut flags = vec![false; len];
+        let (mut pos, mut run) = (0, 1);
+        while pos < len {
+            let end = (pos + run).min(len);
+            flags[pos..end].fill(true);
+            pos = end + run % 3 + 1;
+            run += 1;
+        }
+        flags
+    }
+
+    fn flags_of(sf: &impl Skipfield) -> Vec<bool> {
+        (0..sf.len()).map(|i| sf.is_skipped(i)).collect()
+    }
+
+    #[test]
+    fn test_round_trips_preserve_every_slot() {
+        for len in [0, 1, 63, 64, 65, 200, 1000] {
+            let flags = pattern(len);
+            let bools = BoolSkipfield::from_flags(flags.clone());
+
+            let bitmask = BitmaskSkipfield::from(&bools);
+            assert_eq!(flags_of(&bitmask), flags);
+            assert_eq!(BoolSkipfield::from(&bitmask).flags(), &flags[..]);
+
+            let lcjc: LCJCSkipfield<u16> = LCJCSkipfield::try_from(&bitmask).unwrap();
+            assert_eq!(flags_of(&lcjc), flags);
+            assert_eq!(lcjc.skipped_runs().collect::<Vec<_>>(), bitmask.skipped_runs().collect::<Vec<_>>());
+            assert_eq!(flags_of(&BitmaskSkipfield::from(&lcjc)), flags);
+            assert_eq!(BitmaskSkipfield::from(&lcjc).words(), bitmask.words());
+
+            let lcjc: LCJCSkipfield<u16> = LCJCSkipfield::try_from(&bools).unwrap();
+            assert_eq!(BoolSkipfield::from(&lcjc).flags(), &flags[..]);
+        }
+    }
+
+    #[test]
+    fn test_converted_lcjc_stays_consistent() {
+        let mut lcjc: LCJCSkipfield = LCJCSkipfield::try_from(&BoolSkipfield::from_flags(pattern(300))).unwrap();
+        let mut model = pattern(300);
+        for i in (0..300).step_by(7) {
+            lcjc.unskip(i);
+            model[i] = false;
+        }
+        lcjc.skip_range(100..150);
+        model[100..150].fill(true);
+        assert_eq!(flags_of(&lcjc), model);
+        assert_eq!(lcjc.count_skipped(), model.iter().filter(|&&f| f).count());
+    }
+
+    #[test]
+    fn test_run_too_long_for_node() {
+        let mut bitmask = BitmaskSkipfield::new(1000);
+        bitmask.skip_range(10..20);
+        bitmask.skip_range(300..556);
+
+        let err = LCJCSkipfield::<u8>::try_from(&bitmask).err().unwrap();
+        assert_eq!(err, RunTooLong { start: 300, len: 256, max: 255 });
+        assert!(err.to_string().contains("256"));
+
+        let lcjc: LCJCSkipfield<u16> = LCJCSkipfield::try_from(&bitmask).unwrap();
+        assert!(lcjc.is_range_skipped(300..556));
+        assert!(!lcjc.is_skipped(556));
+    }
+}
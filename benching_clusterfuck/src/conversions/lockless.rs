@@ -0,0 +1,127 @@
+//! Conversions between `BitmaskSkipfield` and the skipfields of
+//! `lockless_boolean_skipfield`. Both sides use the same words, so going
+//! either way is a copy. Converting a lockless field takes a snapshot with
+//! `load_words`; see there for what it guarantees while writers are running.
+
+use lockless_boolean_skipfield::{LockLessBitmaskSkipfield, LockLessBoolSkipfield};
+use skipfield_core::Skipfield;
+
+use crate::bitmask_skipfield::BitmaskSkipfield;
+
+impl From<&BitmaskSkipfield> for LockLessBoolSkipfield {
+    fn from(sf: &BitmaskSkipfield) -> Self {
+        LockLessBoolSkipfield::from_words(sf.words().to_vec(), Skipfield::len(sf))
+    }
+}
+
+impl From<&LockLessBoolSkipfield> for BitmaskSkipfield {
+    fn from(sf: &LockLessBoolSkipfield) -> Self {
+        BitmaskSkipfield::from_words(sf.load_words(), Skipfield::len(sf))
+    }
+}
+
+impl From<&BitmaskSkipfield> for LockLessBitmaskSkipfield {
+    fn from(sf: &BitmaskSkipfield) -> Self {
+        LockLessBitmaskSkipfield::from_words(sf.words().to_vec(), Skipfield::len(sf))
+    }
+}
+
+impl From<&LockLessBitmaskSkipfield> for BitmaskSkipfield {
+    fn from(sf: &LockLessBitmaskSkipfield) -> Self {
+        BitmaskSkipfield::from_words(sf.load_words(), sf.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let sf = LockLessBoolSkipfield::new(100);
+        for i in (0..100).step_by(3) {
+            sf.unskip(i);
+        }
+
+        let snap = BitmaskSkipfield::from(&sf);
+        assert_eq!(snap.count_active(), 34);
+        assert_eq!(snap.iter().collect::<Vec<_>>(), sf.alive_indices().collect::<Vec<_>>());
+
+        let seeded = LockLessBoolSkipfield::from(&snap);
+        assert_eq!(seeded.alive_indices().collect::<Vec<_>>(), sf.alive_indices().collect::<Vec<_>>());
+
+        let sf = LockLessBitmaskSkipfield::new(130);
+        for i in (0..130).step_by(3) {
+            sf.unskip(i);
+        }
+
+        let snap = BitmaskSkipfield::from(&sf);
+        assert_eq!(snap.count_active(), 44);
+        assert_eq!(snap.iter().collect::<Vec<_>>(), sf.alive_indices().collect::<Vec<_>>());
+
+        let seeded = LockLessBitmaskSkipfield::from(&snap);
+        assert_eq!(seeded.alive_indices().collect::<Vec<_>>(), sf.alive_indices().collect::<Vec<_>>());
+        assert_eq!(seeded.claim_n(200).len(), 130 - 44);
+    }
+
+    #[test]
+    fn test_named_snapshot_matches_conversion() {
+        let sf = LockLessBitmaskSkipfield::new(130);
+        for i in (0..130).step_by(3) {
+            sf.unskip(i);
+        }
+
+        let snap: BitmaskSkipfield = sf.snapshot();
+        assert_eq!(snap.words(), BitmaskSkipfield::from(&sf).words());
+        let bools = LockLessBoolSkipfield::from_snapshot(&snap);
+        assert_eq!(bools.snapshot::<BitmaskSkipfield>().words(), snap.words());
+        assert_eq!(LockLessBitmaskSkipfield::from_snapshot(&snap).load_words(), sf.load_words());
+    }
+
+    #[test]
+    fn test_snapshot_during_writes() {
+        const SIZE: usize = 10000;
+
+        let bools = Arc::new(LockLessBoolSkipfield::new(SIZE));
+        let bits = Arc::new(LockLessBitmaskSkipfield::new(SIZE));
+        for i in 0..SIZE / 2 {
+            bools.unskip(i);
+            bits.unskip(i);
+        }
+
+        // the writer only touches words past the middle, so the lower half is stable
+        let (w_bools, w_bits) = (Arc::clone(&bools), Arc::clone(&bits));
+        let writer = thread::spawn(move || {
+            for i in SIZE / 2 + 64..SIZE {
+                w_bools.unskip(i);
+                w_bits.unskip(i);
+            }
+        });
+        let bools_snap = BitmaskSkipfield::from(&*bools);
+        let bits_snap = BitmaskSkipfield::from(&*bits);
+        writer.join().unwrap();
+
+        assert_eq!(bools_snap.count_active_in(0..SIZE / 2), SIZE / 2);
+        assert_eq!(bits_snap.count_active_in(0..SIZE / 2), SIZE / 2);
+        assert_eq!(BitmaskSkipfield::from(&*bools).count_active(), SIZE - 64);
+        assert_eq!(BitmaskSkipfield::from(&*bits).count_active(), SIZE - 64);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_matches_bitmask() {
+        let sf = LockLessBitmaskSkipfield::new(130);
+        for i in (0..130).step_by(7) {
+            sf.unskip(i);
+        }
+        let json = serde_json::to_string(&sf).unwrap();
+        assert_eq!(json, serde_json::to_string(&BitmaskSkipfield::from(&sf)).unwrap());
+
+        let bools: LockLessBoolSkipfield = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&bools).unwrap(), json);
+        let back: BitmaskSkipfield = serde_json::from_str(&serde_json::to_string(&bools).unwrap()).unwrap();
+        assert_eq!(back.iter().collect::<Vec<_>>(), sf.alive_indices().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,87 @@
+//! Conversions to and from `optimized_seq_skipfield::Skipfield`, which stores
+//! the same words as `BitmaskSkipfield`, bit set meaning skipped. It keeps the
+//! bits past `len` clear where the bitmask keeps them set.
+
+use optimized_seq_skipfield::Skipfield as SeqSkipfield;
+use skipfield_core::Skipfield;
+
+use super::RunTooLong;
+use crate::bitmask_skipfield::BitmaskSkipfield;
+use crate::bool_skipfield::BoolSkipfield;
+use crate::lcjc_skipfield::{LCJCNode, LCJCSkipfield};
+use crate::simd;
+
+impl From<&SeqSkipfield> for BitmaskSkipfield {
+    fn from(sf: &SeqSkipfield) -> Self {
+        BitmaskSkipfield::from_words(sf.words().to_vec(), Skipfield::len(sf))
+    }
+}
+
+impl From<&BitmaskSkipfield> for SeqSkipfield {
+    fn from(sf: &BitmaskSkipfield) -> Self {
+        SeqSkipfield::from_words(sf.words().to_vec(), Skipfield::len(sf))
+    }
+}
+
+impl From<&BoolSkipfield> for SeqSkipfield {
+    fn from(sf: &BoolSkipfield) -> Self {
+        SeqSkipfield::from_words(simd::pack_bools(sf.flags()), sf.flags().len())
+    }
+}
+
+impl From<&SeqSkipfield> for BoolSkipfield {
+    fn from(sf: &SeqSkipfield) -> Self {
+        BoolSkipfield::from(&BitmaskSkipfield::from(sf))
+    }
+}
+
+impl<N: LCJCNode> TryFrom<&SeqSkipfield> for LCJCSkipfield<N> {
+    type Error = RunTooLong;
+
+    fn try_from(sf: &SeqSkipfield) -> Result<Self, RunTooLong> {
+        LCJCSkipfield::try_from(&BitmaskSkipfield::from(sf))
+    }
+}
+
+impl<N: LCJCNode> From<&LCJCSkipfield<N>> for SeqSkipfield {
+    fn from(sf: &LCJCSkipfield<N>) -> Self {
+        SeqSkipfield::from(&BitmaskSkipfield::from(sf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skipped(len: usize) -> Vec<bool> {
+        (0..len).map(|i| i % 5 < 2 || (300..700).contains(&i)).collect()
+    }
+
+    #[test]
+    fn test_round_trips_keep_words_and_padding() {
+        for len in [0, 1, 64, 100, 1000] {
+            let flags = skipped(len);
+            let bools = BoolSkipfield::from_flags(flags.clone());
+
+            let seq = SeqSkipfield::from(&bools);
+            assert_eq!(Skipfield::len(&seq), len);
+            assert_eq!(seq.words(), simd::pack_bools(&flags));
+
+            let bitmask = BitmaskSkipfield::from(&seq);
+            assert_eq!(bitmask.words(), BitmaskSkipfield::from_flags(&flags).words());
+            assert_eq!(SeqSkipfield::from(&bitmask).words(), seq.words());
+            assert_eq!(BoolSkipfield::from(&seq).flags(), &flags[..]);
+
+            let lcjc: LCJCSkipfield<u16> = LCJCSkipfield::try_from(&seq).unwrap();
+            assert_eq!(SeqSkipfield::from(&lcjc).words(), seq.words());
+        }
+    }
+
+    #[test]
+    fn test_long_run_doesnt_fit_u8() {
+        let seq = SeqSkipfield::from(&BoolSkipfield::from_flags(skipped(1000)));
+        let err = LCJCSkipfield::<u8>::try_from(&seq).err().unwrap();
+        assert_eq!(err.start, 300);
+        assert_eq!(err.max, 255);
+    }
+}
@@ -0,0 +1,497 @@
+use std::ops::Range;
+
+use skipfield_core::Skipfield;
+
+use crate::lcjc_skipfield::{active_gaps, LCJCNode, LCJCSkipfieldIter};
+
+/// High-complexity jump-counting skipfield.
+///
+/// Every node in a skipblock holds its distance to the end of the block,
+/// except the end node, which holds the block length like the start node
+/// does. Start and end nodes look exactly like `LCJCSkipfield`'s, but any
+/// skipped node can find its block in O(1), at the cost of rewriting the
+/// nodes in front of every split or merge point.
+pub struct HCJCSkipfield<N: LCJCNode = u8> {
+    nodes: Vec<N>,
+}
+
+impl HCJCSkipfield {
+    pub fn new(size: usize) -> Self {
+        Self::with_len(size)
+    }
+}
+
+impl<N: LCJCNode> HCJCSkipfield<N> {
+    pub fn with_len(size: usize) -> Self {
+        Self { nodes: vec![N::ZERO; size] }
+    }
+
+    pub fn push(&mut self, skipped: bool) {
+        self.resize(self.nodes.len() + 1, skipped);
+    }
+
+    pub fn resize(&mut self, new_len: usize, skipped: bool) {
+        let old_len = self.nodes.len();
+        if new_len <= old_len {
+            self.truncate(new_len);
+            return;
+        }
+
+        self.nodes.resize(new_len, N::ZERO);
+        if skipped {
+            self.skip_range(old_len..new_len);
+        }
+    }
+
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len >= self.nodes.len() {
+            return;
+        }
+
+        if new_len > 0 && self.is_skipped(new_len - 1) && self.is_skipped(new_len) {
+            let (start, _) = self.block_bounds(new_len - 1);
+            self.nodes.truncate(new_len);
+            self.write_block(start, new_len - 1, new_len - 1);
+        } else {
+            self.nodes.truncate(new_len);
+        }
+    }
+
+    pub fn trim_trailing_skipped(&mut self) {
+        if self.nodes.last().is_some_and(|&last| last != N::ZERO) {
+            let (start, _) = self.block_bounds(self.nodes.len() - 1);
+            self.nodes.truncate(start);
+        }
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.nodes.shrink_to_fit();
+    }
+
+    pub fn skip(&mut self, i: usize) {
+        if self.is_skipped(i) {
+            return;
+        }
+
+        // neighbours of an active node are an end node and a start node, both
+        // holding their block length
+        let start = if i > 0 && self.is_skipped(i - 1) { i - self.nodes[i - 1].to_usize() } else { i };
+        let end = if i + 1 < self.nodes.len() && self.is_skipped(i + 1) { i + self.nodes[i + 1].to_usize() } else { i };
+
+        // the right block's distances to `end` are still correct
+        self.write_block(start, end, i + 1);
+    }
+
+    pub fn unskip(&mut self, i: usize) {
+        if !self.is_skipped(i) {
+            return;
+        }
+
+        let (start, end) = self.block_bounds(i);
+        self.nodes[i] = N::ZERO;
+        if start < i {
+            self.write_block(start, i - 1, i - 1);
+        }
+        if i < end {
+            self.write_block(i + 1, end, i + 1);
+        }
+    }
+
+    pub fn is_skipped(&self, i: usize) -> bool {
+        self.nodes[i] != N::ZERO
+    }
+
+    /// Start and end of the skipblock holding `i`, if `i` is skipped.
+    pub fn skipblock(&self, i: usize) -> Option<(usize, usize)> {
+        self.is_skipped(i).then(|| self.block_bounds(i))
+    }
+
+    pub fn skip_range(&mut self, range: Range<usize>) {
+        assert!(range.end <= self.nodes.len(), "range end {} out of bounds (len = {})", range.end, self.nodes.len());
+        if range.start >= range.end {
+            return;
+        }
+
+        let start = if range.start > 0 && self.is_skipped(range.start - 1) {
+            self.block_bounds(range.start - 1).0
+        } else {
+            range.start
+        };
+        let end = if range.end < self.nodes.len() && self.is_skipped(range.end) {
+            self.block_bounds(range.end).1
+        } else {
+            range.end - 1
+        };
+
+        self.write_block(start, end, range.end);
+    }
+
+    pub fn unskip_range(&mut self, range: Range<usize>) {
+        assert!(range.end <= self.nodes.len(), "range end {} out of bounds (len = {})", range.end, self.nodes.len());
+        if range.start >= range.end {
+            return;
+        }
+
+        // blocks hanging over either side of the range
+        let left = (range.start > 0 && self.is_skipped(range.start - 1))
+            .then(|| self.block_bounds(range.start - 1))
+            .filter(|&(_, end)| end >= range.start);
+        let right = (range.end < self.nodes.len() && self.is_skipped(range.end))
+            .then(|| self.block_bounds(range.end))
+            .filter(|&(start, _)| start < range.end);
+
+        self.nodes[range.clone()].fill(N::ZERO);
+        if let Some((start, _)) = left {
+            self.write_block(start, range.start - 1, range.start - 1);
+        }
+        if let Some((_, end)) = right {
+            self.write_block(range.end, end, range.end);
+        }
+    }
+
+    pub fn is_range_skipped(&self, range: Range<usize>) -> bool {
+        !self.any_active_in(range)
+    }
+
+    pub fn any_active_in(&self, range: Range<usize>) -> bool {
+        self.active_in(range).next().is_some()
+    }
+
+    pub fn count_active_in(&self, range: Range<usize>) -> usize {
+        self.active_in(range).count()
+    }
+
+    fn active_in(&self, range: Range<usize>) -> impl Iterator<Item = usize> + '_ {
+        let mut i = range.start;
+        std::iter::from_fn(move || {
+            while i < range.end {
+                if !self.is_skipped(i) {
+                    i += 1;
+                    return Some(i - 1);
+                }
+                i = self.block_bounds(i).1 + 1;
+            }
+            None
+        })
+    }
+
+    fn block_bounds(&self, i: usize) -> (usize, usize) {
+        let end = if i + 1 == self.nodes.len() || !self.is_skipped(i + 1) {
+            i
+        } else {
+            i + self.nodes[i].to_usize() - 1
+        };
+        (end + 1 - self.nodes[end].to_usize(), end)
+    }
+
+    // makes `start..=end` one block, rewriting the distances of the nodes in
+    // `start..dirty_end`. nodes from `dirty_end` on must already hold their
+    // distance to `end`
+    fn write_block(&mut self, start: usize, end: usize, dirty_end: usize) {
+        let len = end - start + 1;
+        assert!(
+            len <= N::MAX,
+            "skipblock of {len} slots doesn't fit a node of max {}; use a wider node type",
+            N::MAX
+        );
+        for j in start..dirty_end.min(end) {
+            self.nodes[j] = N::from_usize(end - j + 1);
+        }
+        self.nodes[end] = N::from_usize(len);
+    }
+
+    pub fn count_skipped(&self) -> usize {
+        let mut i = 0;
+        let mut total = 0;
+        while i < self.nodes.len() {
+            let skip = self.nodes[i].to_usize();
+            total += skip;
+            i += skip.max(1);
+        }
+        total
+    }
+
+    pub fn count_active(&self) -> usize {
+        self.nodes.len() - self.count_skipped()
+    }
+
+    pub fn first_active(&self) -> Option<usize> {
+        self.active_in(0..self.nodes.len()).next()
+    }
+
+    pub fn active_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.iter()
+    }
+
+    pub fn skipped_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.skipped_runs().flat_map(|(start, len)| start..start + len)
+    }
+
+    /// Maximal runs of active slots as `(start, len)`.
+    pub fn active_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        active_gaps(self.skipped_runs(), self.nodes.len())
+    }
+
+    /// Maximal runs of skipped slots as `(start, len)`.
+    pub fn skipped_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let mut i = 0;
+        std::iter::from_fn(move || {
+            while i < self.nodes.len() {
+                if self.is_skipped(i) {
+                    let start = i;
+                    i = self.block_bounds(i).1 + 1;
+                    return Some((start, i - start));
+                }
+                i += 1;
+            }
+            None
+        })
+    }
+
+    pub fn debug(&self) -> &[N] {
+        &self.nodes
+    }
+
+    pub fn iter(&self) -> LCJCSkipfieldIter<'_, N> {
+        LCJCSkipfieldIter::new(&self.nodes)
+    }
+
+    /// Active indices from `idx` on.
+    pub fn iter_from(&self, idx: usize) -> LCJCSkipfieldIter<'_, N> {
+        let front = if idx < self.nodes.len() && self.is_skipped(idx) {
+            self.block_bounds(idx).1 + 1
+        } else {
+            idx.min(self.nodes.len())
+        };
+        LCJCSkipfieldIter::starting_at(&self.nodes, front)
+    }
+}
+
+impl<N: LCJCNode> Skipfield for HCJCSkipfield<N> {
+    const MAX_SKIPPED_RUN: usize = N::MAX;
+
+    fn new(len: usize) -> Self {
+        HCJCSkipfield::with_len(len)
+    }
+
+    fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn skip(&mut self, idx: usize) {
+        HCJCSkipfield::skip(self, idx)
+    }
+
+    fn unskip(&mut self, idx: usize) {
+        HCJCSkipfield::unskip(self, idx)
+    }
+
+    fn is_skipped(&self, idx: usize) -> bool {
+        HCJCSkipfield::is_skipped(self, idx)
+    }
+
+    fn first_active(&self) -> Option<usize> {
+        HCJCSkipfield::first_active(self)
+    }
+
+    fn count_skipped(&self) -> usize {
+        HCJCSkipfield::count_skipped(self)
+    }
+
+    fn skip_range(&mut self, range: Range<usize>) {
+        HCJCSkipfield::skip_range(self, range)
+    }
+
+    fn unskip_range(&mut self, range: Range<usize>) {
+        HCJCSkipfield::unskip_range(self, range)
+    }
+
+    fn is_range_skipped(&self, range: Range<usize>) -> bool {
+        HCJCSkipfield::is_range_skipped(self, range)
+    }
+
+    fn any_active_in(&self, range: Range<usize>) -> bool {
+        HCJCSkipfield::any_active_in(self, range)
+    }
+
+    fn count_active_in(&self, range: Range<usize>) -> usize {
+        HCJCSkipfield::count_active_in(self, range)
+    }
+
+    fn active_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.iter()
+    }
+
+    fn skipped_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        HCJCSkipfield::skipped_indices(self)
+    }
+
+    fn active_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        HCJCSkipfield::active_runs(self)
+    }
+
+    fn skipped_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        HCJCSkipfield::skipped_runs(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use skipfield_core::differential::{self, Config};
+    use skipfield_core::skipfield_conformance_tests;
+
+    #[test]
+    fn test_skip_and_counting() {
+        let mut sf = HCJCSkipfield::new(10);
+        assert_eq!(sf.count_skipped(), 0);
+        assert_eq!(sf.count_active(), 10);
+        assert_eq!(sf.first_active(), Some(0));
+
+        sf.skip(2);
+        assert!(sf.is_skipped(2));
+        assert_eq!(sf.count_skipped(), 1);
+
+        sf.skip(3);
+        sf.skip(4);
+        assert!(sf.is_skipped(3));
+        assert!(sf.is_skipped(4));
+        assert_eq!(sf.count_skipped(), 3);
+        assert_eq!(sf.count_active(), 7);
+    }
+
+    #[test]
+    fn test_nodes_hold_distance_to_block_end() {
+        let mut sf = HCJCSkipfield::new(8);
+        sf.skip(5);
+        sf.skip(1);
+        sf.skip(3);
+        sf.skip(2);
+        sf.skip(4);
+        assert_eq!(sf.debug(), &[0, 5, 4, 3, 2, 5, 0, 0]);
+
+        sf.unskip(3);
+        assert_eq!(sf.debug(), &[0, 2, 2, 0, 2, 2, 0, 0]);
+        assert_eq!(sf.skipblock(2), Some((1, 2)));
+        assert_eq!(sf.skipblock(4), Some((4, 5)));
+        assert_eq!(sf.skipblock(3), None);
+    }
+
+    #[test]
+    fn test_unskip_without_hints() {
+        let mut sf = HCJCSkipfield::new(12);
+        sf.skip_range(1..11);
+
+        sf.unskip(1);
+        sf.unskip(10);
+        sf.unskip(4);
+        sf.unskip(7);
+        assert_eq!(sf.active_indices().collect::<Vec<_>>(), vec![0, 1, 4, 7, 10, 11]);
+        assert_eq!(sf.count_skipped(), 6);
+        assert_eq!(sf.skipblock(9), Some((8, 9)));
+
+        for i in 0..12 {
+            sf.unskip(i);
+        }
+        assert_eq!(sf.count_skipped(), 0);
+    }
+
+    #[test]
+    fn test_first_active_edge_cases() {
+        let mut sf = HCJCSkipfield::new(4);
+        assert_eq!(sf.first_active(), Some(0));
+
+        sf.skip(0);
+        assert_eq!(sf.first_active(), Some(1));
+
+        sf.skip(1);
+        sf.skip(2);
+        sf.skip(3);
+        assert_eq!(sf.first_active(), None);
+    }
+
+    #[test]
+    fn test_range_operations() {
+        let mut sf = HCJCSkipfield::new(20);
+        sf.skip(2);
+        sf.skip(3);
+        sf.skip(9);
+        sf.skip(10);
+        sf.skip_range(4..9);
+        assert_eq!(sf.skipblock(6), Some((2, 10)));
+
+        sf.unskip_range(5..7);
+        assert_eq!(sf.skipblock(3), Some((2, 4)));
+        assert_eq!(sf.skipblock(8), Some((7, 10)));
+        assert_eq!(sf.active_indices().collect::<Vec<_>>(), vec![0, 1, 5, 6, 11, 12, 13, 14, 15, 16, 17, 18, 19]);
+
+        assert!(sf.is_range_skipped(7..11));
+        assert!(sf.any_active_in(3..6));
+        assert_eq!(sf.count_active_in(3..12), 3);
+    }
+
+    #[test]
+    fn test_iter_from_arbitrary_index() {
+        let mut sf = HCJCSkipfield::new(12);
+        sf.skip_range(2..5);
+        sf.skip_range(8..10);
+
+        assert_eq!(sf.iter_from(3).collect::<Vec<_>>(), vec![5, 6, 7, 10, 11]);
+        assert_eq!(sf.iter_from(4).len(), 5);
+        assert_eq!(sf.iter_from(6).collect::<Vec<_>>(), vec![6, 7, 10, 11]);
+        assert_eq!(sf.iter_from(9).rev().collect::<Vec<_>>(), vec![11, 10]);
+        assert_eq!(sf.iter_from(12).next(), None);
+        assert_eq!(sf.iter().rev().collect::<Vec<_>>(), vec![11, 10, 7, 6, 5, 1, 0]);
+    }
+
+    #[test]
+    fn test_resize_and_truncate() {
+        let mut sf = HCJCSkipfield::new(6);
+        sf.skip_range(3..6);
+        sf.resize(10, true);
+        assert_eq!(sf.skipblock(4), Some((3, 9)));
+
+        sf.truncate(5);
+        assert_eq!(sf.skipblock(3), Some((3, 4)));
+        sf.push(false);
+        sf.push(true);
+        assert_eq!(sf.active_indices().collect::<Vec<_>>(), vec![0, 1, 2, 5]);
+
+        sf.trim_trailing_skipped();
+        assert_eq!(Skipfield::len(&sf), 6);
+    }
+
+    #[test]
+    fn test_wide_nodes() {
+        let mut sf = HCJCSkipfield::<u32>::with_len(10_000);
+        sf.skip_range(0..9_000);
+        assert_eq!(sf.skipblock(4_500), Some((0, 8_999)));
+        sf.unskip(4_500);
+        assert_eq!(sf.skipblock(4_499), Some((0, 4_499)));
+        assert_eq!(sf.skipblock(4_501), Some((4_501, 8_999)));
+        assert_eq!(sf.count_active(), 1_001);
+    }
+
+    #[test]
+    fn test_runs_and_skipped_indices() {
+        let mut sf = HCJCSkipfield::new(10);
+        sf.skip_range(3..6);
+        sf.skip_range(8..10);
+
+        assert_eq!(sf.skipped_runs().collect::<Vec<_>>(), vec![(3, 3), (8, 2)]);
+        assert_eq!(sf.active_runs().collect::<Vec<_>>(), vec![(0, 3), (6, 2)]);
+        assert_eq!(sf.skipped_indices().collect::<Vec<_>>(), vec![3, 4, 5, 8, 9]);
+    }
+
+    skipfield_conformance_tests!(HCJCSkipfield);
+    skipfield_conformance_tests!(conformance_u16: HCJCSkipfield<u16>);
+
+    #[test]
+    fn test_matches_model_with_long_runs() {
+        differential::check::<HCJCSkipfield<u16>>(&Config::default());
+    }
+}
@@ -0,0 +1,325 @@
+use std::mem::MaybeUninit;
+
+use skipfield_core::Skipfield;
+
+use crate::bitmask_skipfield::BitmaskSkipfield;
+
+const DEFAULT_BLOCK_CAPACITY: usize = 64;
+
+struct Block<T, S> {
+    slots: Box<[MaybeUninit<T>]>,
+    // skipped slots are the ones that don't hold an element
+    skipfield: S,
+    // number of skipped slots
+    free: usize,
+}
+
+impl<T, S: Skipfield> Block<T, S> {
+    fn new(capacity: usize) -> Self {
+        let mut skipfield = S::new(capacity);
+        skipfield.skip_range(0..capacity);
+        Self {
+            slots: (0..capacity).map(|_| MaybeUninit::uninit()).collect(),
+            skipfield,
+            free: capacity,
+        }
+    }
+}
+
+/// Unordered container with stable element addresses, in the style of
+/// `plf::colony`.
+///
+/// Elements live in fixed-size blocks that are never reallocated. Every block
+/// carries a skipfield marking its empty slots, so erasing is O(1) and
+/// iteration jumps straight over erased slots. Keys returned by `insert` stay
+/// valid until that element is erased. Inserts go to the block that most
+/// recently had a slot freed, into its lowest free slot, so finding the slot
+/// takes one `first_skipped` over a single block whatever the hive's size.
+///
+/// New blocks start out fully skipped, so the block capacity can't be more
+/// than `S::MAX_SKIPPED_RUN`, e.g. 255 for `LCJCSkipfield<u8>`.
+pub struct Hive<T, S: Skipfield = BitmaskSkipfield> {
+    blocks: Vec<Block<T, S>>,
+    // stack of exactly the blocks with at least one free slot. inserts only
+    // ever fill the top one, so a block leaves the stack by being popped
+    free_blocks: Vec<usize>,
+    block_capacity: usize,
+    len: usize,
+}
+
+impl<T, S: Skipfield> Hive<T, S> {
+    pub fn new() -> Self {
+        Self::with_block_capacity(DEFAULT_BLOCK_CAPACITY)
+    }
+
+    pub fn with_block_capacity(block_capacity: usize) -> Self {
+        assert!(block_capacity > 0, "block capacity must be non-zero");
+        assert!(
+            block_capacity <= S::MAX_SKIPPED_RUN,
+            "block capacity {block_capacity} doesn't fit a skipfield with runs of at most {}",
+            S::MAX_SKIPPED_RUN
+        );
+        Self {
+            blocks: Vec::new(),
+            free_blocks: Vec::new(),
+            block_capacity,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.blocks.len() * self.block_capacity
+    }
+
+    pub fn insert(&mut self, value: T) -> usize {
+        let block_i = match self.free_blocks.last() {
+            Some(&b) => b,
+            None => {
+                self.blocks.push(Block::new(self.block_capacity));
+                self.free_blocks.push(self.blocks.len() - 1);
+                self.blocks.len() - 1
+            }
+        };
+
+        let block = &mut self.blocks[block_i];
+        let slot = block.skipfield.first_skipped().expect("free block has no free slot");
+        block.free -= 1;
+        if block.free == 0 {
+            self.free_blocks.pop();
+        }
+
+        block.slots[slot].write(value);
+        block.skipfield.unskip(slot);
+        self.len += 1;
+        block_i * self.block_capacity + slot
+    }
+
+    pub fn erase(&mut self, key: usize) -> Option<T> {
+        if !self.contains(key) {
+            return None;
+        }
+
+        let (block_i, slot) = self.locate(key);
+        let block = &mut self.blocks[block_i];
+        block.skipfield.skip(slot);
+        if block.free == 0 {
+            self.free_blocks.push(block_i);
+        }
+        block.free += 1;
+        self.len -= 1;
+
+        // SAFETY: the slot was active, so it holds an initialized value, and
+        // it is now marked skipped so it won't be read again
+        Some(unsafe { block.slots[slot].assume_init_read() })
+    }
+
+    pub fn contains(&self, key: usize) -> bool {
+        let (block_i, slot) = self.locate(key);
+        block_i < self.blocks.len() && !self.blocks[block_i].skipfield.is_skipped(slot)
+    }
+
+    pub fn get(&self, key: usize) -> Option<&T> {
+        if !self.contains(key) {
+            return None;
+        }
+        let (block_i, slot) = self.locate(key);
+        // SAFETY: active slots are always initialized
+        Some(unsafe { self.blocks[block_i].slots[slot].assume_init_ref() })
+    }
+
+    pub fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        if !self.contains(key) {
+            return None;
+        }
+        let (block_i, slot) = self.locate(key);
+        // SAFETY: active slots are always initialized
+        Some(unsafe { self.blocks[block_i].slots[slot].assume_init_mut() })
+    }
+
+    pub fn clear(&mut self) {
+        let block_capacity = self.block_capacity;
+        for block in &mut self.blocks {
+            let active: Vec<usize> = block.skipfield.active_indices().collect();
+            for slot in active {
+                block.skipfield.skip(slot);
+                // SAFETY: the slot was active, so it holds an initialized value
+                unsafe { block.slots[slot].assume_init_drop() };
+            }
+            block.free = block_capacity;
+        }
+        // lowest block on top, so refilling goes block by block from the start
+        self.free_blocks = (0..self.blocks.len()).rev().collect();
+        self.len = 0;
+    }
+
+    /// Keys of all elements, block by block.
+    pub fn keys(&self) -> impl Iterator<Item = usize> + '_ {
+        let block_capacity = self.block_capacity;
+        self.blocks.iter().enumerate().flat_map(move |(block_i, block)| {
+            block
+                .skipfield
+                .active_indices()
+                .map(move |slot| block_i * block_capacity + slot)
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.blocks.iter().flat_map(|block| {
+            block
+                .skipfield
+                .active_indices()
+                // SAFETY: active slots are always initialized
+                .map(move |slot| unsafe { block.slots[slot].assume_init_ref() })
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        self.blocks.iter_mut().flat_map(|block| {
+            let slots = block.slots.as_mut_ptr();
+            block
+                .skipfield
+                .active_indices()
+                // SAFETY: active slots are always initialized and the skipfield
+                // yields every index at most once, so no two references alias
+                .map(move |slot| unsafe { (*slots.add(slot)).assume_init_mut() })
+        })
+    }
+
+    #[inline]
+    fn locate(&self, key: usize) -> (usize, usize) {
+        (key / self.block_capacity, key % self.block_capacity)
+    }
+}
+
+impl<T, S: Skipfield> Default for Hive<T, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, S: Skipfield> Drop for Hive<T, S> {
+    fn drop(&mut self) {
+        if std::mem::needs_drop::<T>() {
+            self.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lcjc_skipfield::LCJCSkipfield;
+    use std::rc::Rc;
+
+    fn insert_erase_reuse<S: Skipfield>() {
+        let mut hive: Hive<u32, S> = Hive::with_block_capacity(8);
+        let keys: Vec<usize> = (0..20).map(|v| hive.insert(v)).collect();
+        assert_eq!(hive.len(), 20);
+        assert_eq!(hive.capacity(), 24);
+
+        assert_eq!(hive.erase(keys[3]), Some(3));
+        assert_eq!(hive.erase(keys[3]), None);
+        assert_eq!(hive.erase(keys[9]), Some(9));
+        assert_eq!(hive.len(), 18);
+        assert_eq!(hive.get(keys[3]), None);
+        assert_eq!(hive.get(keys[4]), Some(&4));
+
+        // the block freed last is refilled first, then the one before it
+        assert_eq!(hive.insert(100), keys[9]);
+        assert_eq!(hive.insert(101), keys[3]);
+        assert_eq!(hive.insert(102), 20);
+        assert_eq!(hive.capacity(), 24);
+
+        let mut values: Vec<u32> = hive.iter().copied().collect();
+        values.sort();
+        let mut expected: Vec<u32> = (0..20).filter(|&v| v != 3 && v != 9).collect();
+        expected.extend([100, 101, 102]);
+        assert_eq!(values, expected);
+
+        // within a block the lowest free slot goes first
+        hive.erase(keys[6]);
+        hive.erase(keys[1]);
+        assert_eq!(hive.insert(103), keys[1]);
+        assert_eq!(hive.insert(104), keys[6]);
+    }
+
+    #[test]
+    fn test_insert_erase_reuse_bitmask() {
+        insert_erase_reuse::<BitmaskSkipfield>();
+    }
+
+    #[test]
+    fn test_insert_erase_reuse_lcjc() {
+        insert_erase_reuse::<LCJCSkipfield>();
+    }
+
+    #[test]
+    fn test_block_capacity_up_to_the_node_limit() {
+        let mut hive: Hive<u8, LCJCSkipfield<u8>> = Hive::with_block_capacity(255);
+        for v in 0..=255 {
+            hive.insert(v);
+        }
+        assert_eq!(hive.capacity(), 510);
+        assert_eq!(hive.erase(254), Some(254));
+        assert_eq!(hive.insert(0), 254);
+    }
+
+    #[test]
+    #[should_panic(expected = "block capacity 256")]
+    fn test_rejects_block_capacity_over_the_node_limit() {
+        Hive::<u8, LCJCSkipfield<u8>>::with_block_capacity(256);
+    }
+
+    #[test]
+    fn test_stable_addresses() {
+        let mut hive: Hive<u64> = Hive::with_block_capacity(4);
+        let first = hive.insert(7);
+        let addr = hive.get(first).unwrap() as *const u64;
+        for v in 0..100 {
+            hive.insert(v);
+        }
+        assert_eq!(hive.get(first).unwrap() as *const u64, addr);
+    }
+
+    #[test]
+    fn test_iter_mut_and_keys() {
+        let mut hive: Hive<u32> = Hive::new();
+        let keys: Vec<usize> = (0..100).map(|v| hive.insert(v)).collect();
+        for &k in keys.iter().step_by(2) {
+            hive.erase(k);
+        }
+        for v in hive.iter_mut() {
+            *v *= 10;
+        }
+
+        let collected: Vec<(usize, u32)> = hive.keys().map(|k| (k, *hive.get(k).unwrap())).collect();
+        let expected: Vec<(usize, u32)> = keys.iter().skip(1).step_by(2).map(|&k| (k, k as u32 * 10)).collect();
+        assert_eq!(collected, expected);
+    }
+
+    #[test]
+    fn test_drops_remaining_elements() {
+        let tracker = Rc::new(());
+        let mut hive: Hive<Rc<()>, LCJCSkipfield> = Hive::with_block_capacity(16);
+        let keys: Vec<usize> = (0..40).map(|_| hive.insert(Rc::clone(&tracker))).collect();
+        drop(hive.erase(keys[5]));
+        assert_eq!(Rc::strong_count(&tracker), 40);
+
+        hive.clear();
+        assert_eq!(Rc::strong_count(&tracker), 1);
+        assert!(hive.is_empty());
+
+        let refilled: Vec<usize> = (0..10).map(|_| hive.insert(Rc::clone(&tracker))).collect();
+        assert_eq!(refilled, (0..10).collect::<Vec<_>>());
+        drop(hive);
+        assert_eq!(Rc::strong_count(&tracker), 1);
+    }
+}
@@ -1,88 +1,358 @@
-pub struct LCJCSkipfield {
-    nodes: Vec<u8>,
+use std::iter::FusedIterator;
+use std::ops::Range;
+
+use skipfield_core::Skipfield;
+
+use crate::conversions::RunTooLong;
+
+/// Integer type stored in each `LCJCSkipfield` node. A skipblock can be at
+/// most `MAX` slots long unless the field chains long runs.
+pub trait LCJCNode: Copy + Eq {
+    const ZERO: Self;
+    const MAX: usize;
+
+    fn to_usize(self) -> usize;
+
+    fn from_usize(n: usize) -> Self;
+}
+
+macro_rules! impl_lcjc_node {
+    ($($t:ty),*) => {
+        $(
+            impl LCJCNode for $t {
+                const ZERO: Self = 0;
+                const MAX: usize = <$t>::MAX as usize;
+
+                #[inline]
+                fn to_usize(self) -> usize {
+                    self as usize
+                }
+
+                #[inline]
+                fn from_usize(n: usize) -> Self {
+                    n as $t
+                }
+            }
+        )*
+    };
+}
+
+impl_lcjc_node!(u8, u16, u32, u64, usize);
+
+/// Low-complexity jump-counting skipfield.
+///
+/// Only the start and end node of a skipblock hold its length; middle nodes
+/// are just non-zero. A run of skipped slots is normally a single block, so
+/// its length must fit in `N`. A chained field instead splits longer runs into
+/// back-to-back sub-blocks of at most `N::MAX` slots, which keeps the compact
+/// `u8` form usable for long runs at the cost of one jump per sub-block.
+#[derive(Clone)]
+pub struct LCJCSkipfield<N: LCJCNode = u8> {
+    nodes: Vec<N>,
+    chained: bool,
 }
 
 impl LCJCSkipfield {
     pub fn new(size: usize) -> Self {
-        Self { nodes: vec![0; size] }
+        Self::with_len(size)
     }
 
-    pub fn skip(&mut self, i: usize) {
-        let left = if i > 0 { self.nodes[i - 1] } else { 0 };
-        let right = if i + 1 < self.nodes.len() { self.nodes[i + 1] } else { 0 };
+    pub fn new_chained(size: usize) -> Self {
+        Self::with_len_chained(size)
+    }
+}
 
-        match (left, right) {
-            (0, 0) => {
-                self.nodes[i] = 1;
-            }
-            (0, r) => {
-                let end = i + r as usize;
-                let val = r + 1;
-                self.nodes[end] = val;
-                self.nodes[i] = val;
-            }
-            (l, 0) => {
-                let start = i - l as usize;
-                let val = l + 1;
-                self.nodes[start] = val;
-                self.nodes[i] = val;
-            }
-            (l, r) => {
-                let start = i - l as usize;
-                let end = i + r as usize;
-                let val = l + r + 1;
-                self.nodes[start] = val;
-                self.nodes[end] = val;
+impl<N: LCJCNode> LCJCSkipfield<N> {
+    pub fn with_len(size: usize) -> Self {
+        Self { nodes: vec![N::ZERO; size], chained: false }
+    }
+
+    pub fn with_len_chained(size: usize) -> Self {
+        Self { nodes: vec![N::ZERO; size], chained: true }
+    }
+
+    // builds a field from sorted, non-touching skipped runs in one pass,
+    // without looking at neighbours the way `skip_range` does
+    pub(crate) fn from_runs(
+        len: usize,
+        runs: impl IntoIterator<Item = (usize, usize)>,
+        chained: bool,
+    ) -> Result<Self, RunTooLong> {
+        let mut sf = Self { nodes: vec![N::ZERO; len], chained };
+        for (start, run_len) in runs {
+            if !chained && run_len > N::MAX {
+                return Err(RunTooLong { start, len: run_len, max: N::MAX });
             }
+            sf.nodes[start..start + run_len].fill(N::from_usize(1));
+            sf.write_run(start, start + run_len - 1);
         }
+        Ok(sf)
     }
 
-    pub fn unskip(&mut self, i: usize, start: Option<usize>, end: Option<usize>) {
-        if self.nodes[i] == 0 {
+    pub fn is_chained(&self) -> bool {
+        self.chained
+    }
+
+    pub fn push(&mut self, skipped: bool) {
+        self.resize(self.nodes.len() + 1, skipped);
+    }
+
+    /// Grows the field with slots in the given state, or truncates it. New
+    /// skipped slots join a skipblock that ends the field.
+    pub fn resize(&mut self, new_len: usize, skipped: bool) {
+        let old_len = self.nodes.len();
+        if new_len <= old_len {
+            self.truncate(new_len);
             return;
         }
 
-        match (start, end) {
-            (Some(s), Some(e)) if i > s && i < e => {
-                let left_len = i - s;
-                let right_len = e - i;
-                self.nodes[s] = left_len as u8;
-                self.nodes[i - 1] = left_len as u8;
-                self.nodes[e] = right_len as u8;
-                self.nodes[i + 1] = right_len as u8;
-                self.nodes[i] = 0;
-            }
-            (Some(s), _) if i == s => {
-                let len = self.nodes[s] - 1;
-                let e = i + len as usize;
-                self.nodes[i + 1] = len;
-                self.nodes[e] = len;
-                self.nodes[i] = 0;
+        self.nodes.resize(new_len, N::ZERO);
+        if skipped {
+            self.skip_range(old_len..new_len);
+        }
+    }
+
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len >= self.nodes.len() {
+            return;
+        }
+
+        // a run cut in two keeps only the part in front of the cut
+        if new_len > 0 && self.is_skipped(new_len - 1) && self.is_skipped(new_len) {
+            let start = self.enclosing_run(new_len - 1).0;
+            self.nodes.truncate(new_len);
+            self.write_run(start, new_len - 1);
+        } else {
+            self.nodes.truncate(new_len);
+        }
+    }
+
+    /// Drops the skipped run ending the field, if there is one.
+    pub fn trim_trailing_skipped(&mut self) {
+        if self.nodes.last().is_some_and(|&last| last != N::ZERO) {
+            // the last node is the end node of its run
+            let new_len = self.run_start(self.nodes.len() - 1);
+            self.nodes.truncate(new_len);
+        }
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.nodes.shrink_to_fit();
+    }
+
+    pub fn skip(&mut self, i: usize) {
+        if self.is_skipped(i) {
+            return;
+        }
+
+        // neighbours of an active node are always end/start nodes
+        let start = if i > 0 && self.is_skipped(i - 1) { self.run_start(i - 1) } else { i };
+        let end = if i + 1 < self.nodes.len() && self.is_skipped(i + 1) { self.run_end(i + 1) } else { i };
+
+        // middle nodes only need to be non-zero
+        self.nodes[i] = N::from_usize(1);
+        self.write_run(start, end);
+    }
+
+    pub fn unskip(&mut self, i: usize) {
+        if !self.is_skipped(i) {
+            return;
+        }
+        let (start, end) = self.enclosing_run(i);
+        self.unskip_with_bounds(i, Some(start), Some(end));
+    }
+
+    /// `unskip` for callers that already know the start and end of the
+    /// skipped run holding `i`. Wrong bounds corrupt the field.
+    pub fn unskip_with_bounds(&mut self, i: usize, start: Option<usize>, end: Option<usize>) {
+        if !self.is_skipped(i) {
+            return;
+        }
+        debug_assert!(
+            start.is_none_or(|s| s <= i && (s == 0 || !self.is_skipped(s - 1))),
+            "{start:?} is not the start of the skipped run holding {i}"
+        );
+        debug_assert!(
+            end.is_none_or(|e| e >= i && (e + 1 == self.nodes.len() || !self.is_skipped(e + 1))),
+            "{end:?} is not the end of the skipped run holding {i}"
+        );
+
+        let (start, end) = match (start, end) {
+            (Some(s), Some(e)) => (s, e),
+            (Some(s), None) => (s, self.run_end(s)),
+            (None, Some(e)) => (self.run_start(e), e),
+            (None, None) => self.enclosing_run(i),
+        };
+
+        self.nodes[i] = N::ZERO;
+        if start < i {
+            self.write_run(start, i - 1);
+        }
+        if i < end {
+            self.write_run(i + 1, end);
+        }
+    }
+
+    pub fn is_skipped(&self, i: usize) -> bool {
+        self.nodes[i] != N::ZERO
+    }
+
+    pub fn skip_range(&mut self, range: Range<usize>) {
+        assert!(range.end <= self.nodes.len(), "range end {} out of bounds (len = {})", range.end, self.nodes.len());
+        if range.start >= range.end {
+            return;
+        }
+
+        // the new run swallows any run overlapping or touching the range.
+        // a neighbour next to an active edge of the range is an end/start node
+        // and holds its block length, otherwise we have to walk
+        let start = if range.start > 0 && self.is_skipped(range.start - 1) {
+            if self.is_skipped(range.start) {
+                self.enclosing_run(range.start - 1).0
+            } else {
+                self.run_start(range.start - 1)
             }
-            (_, Some(e)) if i == e => {
-                let len = self.nodes[e] - 1;
-                let s = i - len as usize;
-                self.nodes[i - 1] = len;
-                self.nodes[s] = len;
-                self.nodes[i] = 0;
+        } else {
+            range.start
+        };
+        let end = if range.end < self.nodes.len() && self.is_skipped(range.end) {
+            if self.is_skipped(range.end - 1) {
+                self.enclosing_run(range.end).1
+            } else {
+                self.run_end(range.end)
             }
-            _ => {
-                self.nodes[i] = 0;
+        } else {
+            range.end - 1
+        };
+
+        // middle nodes only need to be non-zero
+        self.nodes[range].fill(N::from_usize(1));
+        self.write_run(start, end);
+    }
+
+    pub fn unskip_range(&mut self, range: Range<usize>) {
+        assert!(range.end <= self.nodes.len(), "range end {} out of bounds (len = {})", range.end, self.nodes.len());
+        if range.start >= range.end {
+            return;
+        }
+
+        // trim the runs hanging over either side of the range
+        let left = (range.start > 0 && self.is_skipped(range.start - 1) && self.is_skipped(range.start))
+            .then(|| self.enclosing_run(range.start).0);
+        let right = (range.end < self.nodes.len() && self.is_skipped(range.end) && self.is_skipped(range.end - 1))
+            .then(|| self.enclosing_run(range.end - 1).1);
+
+        self.nodes[range.clone()].fill(N::ZERO);
+        if let Some(start) = left {
+            self.write_run(start, range.start - 1);
+        }
+        if let Some(end) = right {
+            self.write_run(range.end, end);
+        }
+    }
+
+    pub fn is_range_skipped(&self, range: Range<usize>) -> bool {
+        !self.any_active_in(range)
+    }
+
+    pub fn any_active_in(&self, range: Range<usize>) -> bool {
+        self.active_in(range).next().is_some()
+    }
+
+    pub fn count_active_in(&self, range: Range<usize>) -> usize {
+        self.active_in(range).count()
+    }
+
+    // active indices in `range`, jumping over every run that starts inside it
+    fn active_in(&self, range: Range<usize>) -> impl Iterator<Item = usize> + '_ {
+        let mut i = range.start;
+        std::iter::from_fn(move || {
+            while i < range.end {
+                if !self.is_skipped(i) {
+                    i += 1;
+                    return Some(i - 1);
+                } else if i == 0 || !self.is_skipped(i - 1) {
+                    i = self.run_end(i) + 1;
+                } else {
+                    // inside a run that started before the range
+                    i += 1;
+                }
             }
+            None
+        })
+    }
+
+    // lays out the run `start..=end` as one block, or as a chain of blocks of
+    // at most `N::MAX` slots. only the boundary nodes are written, so every
+    // node in the run must already be non-zero
+    fn write_run(&mut self, start: usize, end: usize) {
+        let len = end - start + 1;
+        if !self.chained {
+            assert!(
+                len <= N::MAX,
+                "skipblock of {len} slots doesn't fit a node of max {}; use a wider node type or a chained field",
+                N::MAX
+            );
+            self.nodes[start] = N::from_usize(len);
+            self.nodes[end] = N::from_usize(len);
+            return;
+        }
+
+        let mut block_start = start;
+        while block_start <= end {
+            let block_len = (end - block_start + 1).min(N::MAX);
+            self.nodes[block_start] = N::from_usize(block_len);
+            self.nodes[block_start + block_len - 1] = N::from_usize(block_len);
+            block_start += block_len;
         }
     }
 
-    pub fn is_skipped(&self, i: usize) -> bool {
-        self.nodes[i] != 0
+    // end of the run starting at block start `start`, following the chain
+    fn run_end(&self, start: usize) -> usize {
+        let mut next = start + self.nodes[start].to_usize();
+        while next < self.nodes.len() && self.is_skipped(next) {
+            next += self.nodes[next].to_usize();
+        }
+        next - 1
+    }
+
+    // start of the run ending at block end `end`, following the chain
+    fn run_start(&self, end: usize) -> usize {
+        let mut start = end + 1 - self.nodes[end].to_usize();
+        while start > 0 && self.is_skipped(start - 1) {
+            start -= self.nodes[start - 1].to_usize();
+        }
+        start
+    }
+
+    // only the start and end nodes of a block hold its length, so the run
+    // around a middle node is found by walking outwards in both directions
+    // until one side reaches a boundary node
+    fn enclosing_run(&self, i: usize) -> (usize, usize) {
+        let (mut left, mut right) = (i, i);
+        loop {
+            if left == 0 || !self.is_skipped(left - 1) {
+                return (left, self.run_end(left));
+            }
+            if right + 1 == self.nodes.len() || !self.is_skipped(right + 1) {
+                return (self.run_start(right), right);
+            }
+            left -= 1;
+            right += 1;
+        }
     }
 
     pub fn count_skipped(&self) -> usize {
         let mut i = 0;
         let mut total = 0;
         while i < self.nodes.len() {
-            let skip = self.nodes[i] as usize;
+            let skip = self.nodes[i].to_usize();
             total += skip;
-            i += skip+1;
+            i += skip.max(1);
         }
         total
     }
@@ -91,12 +361,12 @@ impl LCJCSkipfield {
         let mut i = 0;
         std::iter::from_fn(move || {
             while i < self.nodes.len() {
-                if self.nodes[i] == 0 {
+                if !self.is_skipped(i) {
                     let out = Some(i);
                     i += 1;
                     return out;
                 } else {
-                    i += self.nodes[i] as usize;
+                    i += self.nodes[i].to_usize();
                 }
             }
             None
@@ -110,53 +380,310 @@ impl LCJCSkipfield {
     pub fn first_active(&self) -> Option<usize> {
         let mut i = 0;
         while i < self.nodes.len() {
-            if self.nodes[i] == 0 {
+            if !self.is_skipped(i) {
                 return Some(i);
             } else {
-                i += self.nodes[i] as usize;
+                i += self.nodes[i].to_usize();
             }
         }
         None
     }
 
-    pub fn debug(&self) -> &[u8] {
+    pub fn skipped_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.skipped_runs().flat_map(|(start, len)| start..start + len)
+    }
+
+    /// Maximal runs of active slots as `(start, len)`.
+    pub fn active_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        active_gaps(self.skipped_runs(), self.nodes.len())
+    }
+
+    /// Maximal runs of skipped slots as `(start, len)`.
+    pub fn skipped_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let mut i = 0;
+        std::iter::from_fn(move || {
+            while i < self.nodes.len() {
+                if self.is_skipped(i) {
+                    let start = i;
+                    i = self.run_end(i) + 1;
+                    return Some((start, i - start));
+                }
+                i += 1;
+            }
+            None
+        })
+    }
+
+    // set algebra on the sets of skipped slots, one run at a time
+
+    pub fn union_with<M: LCJCNode>(&mut self, other: &LCJCSkipfield<M>) {
+        self.assert_same_len(other);
+        for (start, len) in other.skipped_runs() {
+            self.skip_range(start..start + len);
+        }
+    }
+
+    pub fn intersect_with<M: LCJCNode>(&mut self, other: &LCJCSkipfield<M>) {
+        self.assert_same_len(other);
+        for (start, len) in other.active_runs() {
+            self.unskip_range(start..start + len);
+        }
+    }
+
+    pub fn difference_with<M: LCJCNode>(&mut self, other: &LCJCSkipfield<M>) {
+        self.assert_same_len(other);
+        for (start, len) in other.skipped_runs() {
+            self.unskip_range(start..start + len);
+        }
+    }
+
+    pub fn symmetric_difference_with<M: LCJCNode>(&mut self, other: &LCJCSkipfield<M>) {
+        self.assert_same_len(other);
+        // every run edge toggles membership; an edge shared by both fields
+        // toggles twice and drops out
+        let mut edges: Vec<usize> = self
+            .skipped_runs()
+            .chain(other.skipped_runs())
+            .flat_map(|(start, len)| [start, start + len])
+            .collect();
+        edges.sort_unstable();
+        let mut toggles: Vec<usize> = Vec::with_capacity(edges.len());
+        for edge in edges {
+            if toggles.last() == Some(&edge) {
+                toggles.pop();
+            } else {
+                toggles.push(edge);
+            }
+        }
+        self.rewrite_runs(toggles.chunks_exact(2).map(|pair| (pair[0], pair[1] - pair[0])));
+    }
+
+    /// Swaps skipped and active slots.
+    pub fn invert(&mut self) {
+        let runs: Vec<(usize, usize)> = self.active_runs().collect();
+        self.rewrite_runs(runs);
+    }
+
+    pub fn union<M: LCJCNode>(&self, other: &LCJCSkipfield<M>) -> Self {
+        let mut out = self.clone();
+        out.union_with(other);
+        out
+    }
+
+    pub fn intersect<M: LCJCNode>(&self, other: &LCJCSkipfield<M>) -> Self {
+        let mut out = self.clone();
+        out.intersect_with(other);
+        out
+    }
+
+    pub fn difference<M: LCJCNode>(&self, other: &LCJCSkipfield<M>) -> Self {
+        let mut out = self.clone();
+        out.difference_with(other);
+        out
+    }
+
+    pub fn symmetric_difference<M: LCJCNode>(&self, other: &LCJCSkipfield<M>) -> Self {
+        let mut out = self.clone();
+        out.symmetric_difference_with(other);
+        out
+    }
+
+    pub fn complement(&self) -> Self {
+        let mut out = self.clone();
+        out.invert();
+        out
+    }
+
+    fn assert_same_len<M: LCJCNode>(&self, other: &LCJCSkipfield<M>) {
+        assert_eq!(self.nodes.len(), other.nodes.len(), "skipfields must have the same length");
+    }
+
+    // replaces the whole field with the given sorted, non-touching runs
+    fn rewrite_runs(&mut self, runs: impl IntoIterator<Item = (usize, usize)>) {
+        self.nodes.fill(N::ZERO);
+        for (start, len) in runs {
+            self.nodes[start..start + len].fill(N::from_usize(1));
+            self.write_run(start, start + len - 1);
+        }
+    }
+
+    pub fn debug(&self) -> &[N] {
         &self.nodes
     }
 
-    pub fn iter(&self) -> LCJCSkipfieldIter<'_> {
+    pub fn iter(&self) -> LCJCSkipfieldIter<'_, N> {
         LCJCSkipfieldIter::new(&self.nodes)
     }
 }
 
-pub struct LCJCSkipfieldIter<'a> {
-    skips: &'a [u8],
+// active runs are the gaps between the skipped ones
+pub(crate) fn active_gaps(
+    mut skipped_runs: impl Iterator<Item = (usize, usize)>,
+    len: usize,
+) -> impl Iterator<Item = (usize, usize)> {
+    let mut pos = 0;
+    std::iter::from_fn(move || loop {
+        if pos >= len {
+            return None;
+        }
+        let (next_start, next_end) = match skipped_runs.next() {
+            Some((start, run_len)) => (start, start + run_len),
+            None => (len, len),
+        };
+        let gap = (pos, next_start - pos);
+        pos = next_end;
+        if gap.1 > 0 {
+            return Some(gap);
+        }
+    })
+}
+
+pub struct LCJCSkipfieldIter<'a, N: LCJCNode = u8> {
+    skips: &'a [N],
+    // next index from the front, and one past the next index from the back
     index: usize,
+    back: usize,
+    remaining: usize,
 }
 
-impl<'a> LCJCSkipfieldIter<'a> {
-    pub fn new(skips: &'a [u8]) -> Self {
-        Self { skips, index: 0 }
+impl<'a, N: LCJCNode> LCJCSkipfieldIter<'a, N> {
+    pub fn new(skips: &'a [N]) -> Self {
+        Self::starting_at(skips, 0)
+    }
+
+    // `index` must be an active node, a block start or `skips.len()`
+    pub(crate) fn starting_at(skips: &'a [N], index: usize) -> Self {
+        let mut skipped = 0;
+        let mut i = index;
+        while i < skips.len() {
+            let skip = skips[i].to_usize();
+            skipped += skip;
+            i += skip.max(1);
+        }
+        Self {
+            skips,
+            index,
+            back: skips.len(),
+            remaining: skips.len() - index - skipped,
+        }
     }
 }
 
-impl<'a> Iterator for LCJCSkipfieldIter<'a> {
+impl<'a, N: LCJCNode> Iterator for LCJCSkipfieldIter<'a, N> {
     type Item = usize;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index >= self.skips.len() {
-            return None;
+        while self.index < self.back {
+            // from the front we only ever land on active nodes or block starts
+            let skip = self.skips[self.index].to_usize();
+            if skip == 0 {
+                self.index += 1;
+                self.remaining -= 1;
+                return Some(self.index - 1);
+            }
+            self.index += skip;
         }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
 
-        let out = self.index;
-        let skip = self.skips[self.index] as usize;
-        self.index += skip + 1;
-        Some(out)
+impl<'a, N: LCJCNode> DoubleEndedIterator for LCJCSkipfieldIter<'a, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while self.back > self.index {
+            // from the back we only ever land on active nodes or block ends
+            let skip = self.skips[self.back - 1].to_usize();
+            if skip == 0 {
+                self.back -= 1;
+                self.remaining -= 1;
+                return Some(self.back);
+            }
+            self.back -= skip;
+        }
+        None
+    }
+}
+
+impl<'a, N: LCJCNode> ExactSizeIterator for LCJCSkipfieldIter<'a, N> {}
+
+impl<'a, N: LCJCNode> FusedIterator for LCJCSkipfieldIter<'a, N> {}
+
+impl<N: LCJCNode> Skipfield for LCJCSkipfield<N> {
+    const MAX_SKIPPED_RUN: usize = N::MAX;
+
+    fn new(len: usize) -> Self {
+        LCJCSkipfield::with_len(len)
+    }
+
+    fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn skip(&mut self, idx: usize) {
+        LCJCSkipfield::skip(self, idx)
+    }
+
+    fn unskip(&mut self, idx: usize) {
+        LCJCSkipfield::unskip(self, idx)
+    }
+
+    fn is_skipped(&self, idx: usize) -> bool {
+        LCJCSkipfield::is_skipped(self, idx)
+    }
+
+    fn first_active(&self) -> Option<usize> {
+        LCJCSkipfield::first_active(self)
+    }
+
+    fn count_skipped(&self) -> usize {
+        LCJCSkipfield::count_skipped(self)
+    }
+
+    fn skip_range(&mut self, range: Range<usize>) {
+        LCJCSkipfield::skip_range(self, range)
+    }
+
+    fn unskip_range(&mut self, range: Range<usize>) {
+        LCJCSkipfield::unskip_range(self, range)
+    }
+
+    fn is_range_skipped(&self, range: Range<usize>) -> bool {
+        LCJCSkipfield::is_range_skipped(self, range)
+    }
+
+    fn any_active_in(&self, range: Range<usize>) -> bool {
+        LCJCSkipfield::any_active_in(self, range)
+    }
+
+    fn count_active_in(&self, range: Range<usize>) -> usize {
+        LCJCSkipfield::count_active_in(self, range)
+    }
+
+    fn active_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.iter()
+    }
+
+    fn skipped_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        LCJCSkipfield::skipped_indices(self)
+    }
+
+    fn active_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        LCJCSkipfield::active_runs(self)
+    }
+
+    fn skipped_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        LCJCSkipfield::skipped_runs(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use skipfield_core::differential::{self, Config};
+    use skipfield_core::skipfield_conformance_tests;
 
     #[test]
     fn test_skip_and_counting() {
@@ -186,7 +713,7 @@ mod tests {
         sf.skip(2);
         sf.skip(3);
 
-        sf.unskip(2, Some(1), Some(3));
+        sf.unskip_with_bounds(2, Some(1), Some(3));
         assert!(!sf.is_skipped(2));
         assert!(sf.is_skipped(1));
         assert!(sf.is_skipped(3));
@@ -201,16 +728,16 @@ mod tests {
         sf.skip(5);
         sf.skip(6);
 
-        sf.unskip(4, Some(4), None);
+        sf.unskip_with_bounds(4, Some(4), None);
         assert!(!sf.is_skipped(4));
         assert!(sf.is_skipped(5));
         assert!(sf.is_skipped(6));
 
-        sf.unskip(6, None, Some(6));
+        sf.unskip_with_bounds(6, None, Some(6));
         assert!(!sf.is_skipped(6));
         assert!(sf.is_skipped(5));
 
-        sf.unskip(5, Some(5), Some(5));
+        sf.unskip_with_bounds(5, Some(5), Some(5));
         assert!(!sf.is_skipped(5));
         assert_eq!(sf.count_skipped(), 0);
         assert_eq!(sf.count_active(), 10);
@@ -256,4 +783,475 @@ mod tests {
         assert_eq!(state[2], state[4]);
         assert_eq!(state[5], 0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_skip_joining_two_blocks() {
+        let mut sf = LCJCSkipfield::new(5);
+        sf.skip(1);
+        sf.skip(3);
+        sf.skip(2);
+
+        assert!(sf.is_skipped(2));
+        assert_eq!(sf.count_skipped(), 3);
+        assert_eq!(sf.debug()[1], 3);
+        assert_eq!(sf.debug()[3], 3);
+
+        sf.skip(2);
+        assert_eq!(sf.count_skipped(), 3);
+    }
+
+    #[test]
+    fn test_unskip_single_node_block_at_end() {
+        let mut sf = LCJCSkipfield::new(3);
+        sf.skip(2);
+        sf.unskip_with_bounds(2, Some(2), Some(2));
+        assert!(!sf.is_skipped(2));
+        assert_eq!(sf.count_skipped(), 0);
+    }
+
+    #[test]
+    fn test_skipfield_trait() {
+        fn exercise<S: Skipfield>(sf: &mut S) -> Vec<usize> {
+            for i in 2..7 {
+                sf.skip(i);
+            }
+            sf.unskip(4);
+            sf.unskip(2);
+            sf.unskip(6);
+            sf.active_indices().collect()
+        }
+
+        let mut sf = <LCJCSkipfield as Skipfield>::new(8);
+        assert_eq!(exercise(&mut sf), vec![0, 1, 2, 4, 6, 7]);
+        assert_eq!(Skipfield::len(&sf), 8);
+        assert_eq!(Skipfield::count_skipped(&sf), 2);
+        assert_eq!(Skipfield::first_active(&sf), Some(0));
+    }
+
+    #[test]
+    fn test_skip_range_merges_neighbouring_blocks() {
+        let mut sf = LCJCSkipfield::new(20);
+        sf.skip(2);
+        sf.skip(3);
+        sf.skip(9);
+        sf.skip(10);
+        sf.skip(15);
+
+        sf.skip_range(4..9);
+        assert_eq!(sf.debug()[2], 9);
+        assert_eq!(sf.debug()[10], 9);
+        assert_eq!(sf.count_skipped(), 10);
+        assert_eq!(sf.first_active(), Some(0));
+        assert_eq!(sf.active_indices().collect::<Vec<_>>(), vec![0, 1, 11, 12, 13, 14, 16, 17, 18, 19]);
+
+        sf.skip_range(12..15);
+        assert_eq!(sf.debug()[12], 4);
+        assert_eq!(sf.debug()[15], 4);
+        assert_eq!(sf.count_skipped(), 13);
+    }
+
+    #[test]
+    fn test_unskip_range_trims_blocks() {
+        let mut sf = LCJCSkipfield::new(20);
+        sf.skip_range(2..18);
+        sf.unskip_range(5..10);
+
+        assert_eq!(sf.debug()[2], 3);
+        assert_eq!(sf.debug()[4], 3);
+        assert_eq!(sf.debug()[10], 8);
+        assert_eq!(sf.debug()[17], 8);
+        assert_eq!(sf.count_skipped(), 11);
+        assert_eq!(sf.active_indices().collect::<Vec<_>>(), vec![0, 1, 5, 6, 7, 8, 9, 18, 19]);
+
+        sf.unskip_range(0..20);
+        assert_eq!(sf.count_skipped(), 0);
+    }
+
+    #[test]
+    fn test_range_queries() {
+        let mut sf = LCJCSkipfield::new(12);
+        sf.skip_range(1..6);
+        sf.skip_range(8..12);
+
+        assert!(sf.is_range_skipped(2..5));
+        assert!(!sf.is_range_skipped(0..3));
+        assert!(sf.any_active_in(3..7));
+        assert!(!sf.any_active_in(9..12));
+        assert_eq!(sf.count_active_in(0..12), 3);
+        assert_eq!(sf.count_active_in(3..9), 2);
+        assert_eq!(sf.count_active_in(4..4), 0);
+    }
+
+    #[test]
+    fn test_iter_skips_blocks() {
+        let mut sf = LCJCSkipfield::new(10);
+        sf.skip(0);
+        sf.skip_range(3..6);
+        sf.skip(9);
+
+        assert_eq!(sf.iter().collect::<Vec<_>>(), vec![1, 2, 6, 7, 8]);
+        assert_eq!(sf.iter().rev().collect::<Vec<_>>(), vec![8, 7, 6, 2, 1]);
+        assert_eq!(sf.iter().len(), sf.count_active());
+    }
+
+    #[test]
+    fn test_iter_double_ended() {
+        let mut sf = LCJCSkipfield::new(12);
+        sf.skip_range(2..5);
+        sf.skip_range(8..10);
+
+        let mut iter = sf.iter();
+        assert_eq!(iter.len(), 7);
+        assert_eq!(iter.next_back(), Some(11));
+        assert_eq!(iter.next_back(), Some(10));
+        assert_eq!(iter.next_back(), Some(7));
+        assert_eq!(iter.next(), Some(0));
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![1, 5, 6]);
+
+        let mut all_skipped = LCJCSkipfield::new(4);
+        all_skipped.skip_range(0..4);
+        assert_eq!(all_skipped.iter().next(), None);
+        assert_eq!(all_skipped.iter().next_back(), None);
+        assert_eq!(LCJCSkipfield::new(0).iter().len(), 0);
+    }
+
+    #[test]
+    fn test_resize_merges_trailing_block() {
+        let mut sf = LCJCSkipfield::new(6);
+        sf.skip_range(3..6);
+        sf.resize(10, true);
+
+        assert_eq!(sf.debug()[3], 7);
+        assert_eq!(sf.debug()[9], 7);
+        assert_eq!(sf.count_skipped(), 7);
+
+        sf.resize(12, false);
+        sf.push(true);
+        assert_eq!(sf.active_indices().collect::<Vec<_>>(), vec![0, 1, 2, 10, 11]);
+        assert_eq!(sf.debug()[12], 1);
+    }
+
+    #[test]
+    fn test_truncate_cuts_block() {
+        let mut sf = LCJCSkipfield::new(10);
+        sf.skip_range(2..9);
+        sf.truncate(5);
+
+        assert_eq!(sf.debug().len(), 5);
+        assert_eq!(sf.debug()[2], 3);
+        assert_eq!(sf.debug()[4], 3);
+        assert_eq!(sf.count_skipped(), 3);
+        assert_eq!(sf.iter().rev().collect::<Vec<_>>(), vec![1, 0]);
+
+        sf.trim_trailing_skipped();
+        assert_eq!(sf.debug(), &[0, 0]);
+        sf.trim_trailing_skipped();
+        assert_eq!(sf.debug(), &[0, 0]);
+
+        sf.reserve(100);
+        sf.shrink_to_fit();
+        assert_eq!(sf.count_active(), 2);
+    }
+
+    #[test]
+    fn test_unskip_finds_block_bounds() {
+        let mut sf = LCJCSkipfield::new(12);
+        sf.skip_range(1..11);
+
+        sf.unskip(1);
+        sf.unskip(10);
+        assert_eq!(sf.debug()[2], 8);
+        assert_eq!(sf.debug()[9], 8);
+
+        sf.unskip(4);
+        assert_eq!(sf.debug()[2], 2);
+        assert_eq!(sf.debug()[3], 2);
+        assert_eq!(sf.debug()[5], 5);
+        assert_eq!(sf.debug()[9], 5);
+
+        sf.unskip(7);
+        sf.unskip(0);
+        assert_eq!(sf.active_indices().collect::<Vec<_>>(), vec![0, 1, 4, 7, 10, 11]);
+        assert_eq!(sf.count_skipped(), 6);
+
+        for i in 0..12 {
+            sf.unskip(i);
+        }
+        assert_eq!(sf.count_skipped(), 0);
+    }
+
+    #[test]
+    fn test_wide_nodes_hold_long_blocks() {
+        let mut sf = LCJCSkipfield::<u16>::with_len(5000);
+        sf.skip_range(10..3000);
+        sf.skip(3000);
+        assert_eq!(sf.debug()[10], 2991);
+        assert_eq!(sf.debug()[3000], 2991);
+        assert_eq!(sf.count_skipped(), 2991);
+
+        sf.unskip(1500);
+        assert_eq!(sf.count_skipped(), 2990);
+        assert_eq!(sf.iter().nth(10), Some(1500));
+        assert_eq!(sf.iter().len(), 2010);
+
+        let mut wide = <LCJCSkipfield<usize> as Skipfield>::new(100_000);
+        wide.skip_range(0..100_000);
+        assert_eq!(wide.first_active(), None);
+        wide.unskip(99_999);
+        assert_eq!(wide.first_active(), Some(99_999));
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't fit a node")]
+    fn test_u8_block_overflow_panics() {
+        let mut sf = LCJCSkipfield::new(300);
+        sf.skip_range(0..256);
+    }
+
+    #[test]
+    fn test_chained_long_runs() {
+        let mut sf = LCJCSkipfield::new_chained(1000);
+        assert!(sf.is_chained());
+        sf.skip_range(100..700);
+        assert_eq!(sf.debug()[100], 255);
+        assert_eq!(sf.debug()[354], 255);
+        assert_eq!(sf.debug()[355], 255);
+        assert_eq!(sf.debug()[610], 90);
+        assert_eq!(sf.debug()[699], 90);
+        assert_eq!(sf.count_skipped(), 600);
+        assert_eq!(sf.count_active(), 400);
+        assert_eq!(sf.iter().nth(100), Some(700));
+        assert_eq!(sf.iter().rev().nth(300), Some(99));
+
+        for i in 700..800 {
+            sf.skip(i);
+        }
+        assert_eq!(sf.count_skipped(), 700);
+        assert_eq!(sf.active_indices().nth(100), Some(800));
+
+        sf.unskip(400);
+        sf.unskip(101);
+        let expected: Vec<usize> = (0..1000).filter(|&i| !(100..800).contains(&i) || i == 400 || i == 101).collect();
+        assert_eq!(sf.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(sf.count_active_in(0..1000), expected.len());
+
+        sf.resize(1500, true);
+        assert_eq!(sf.count_skipped(), 698 + 500);
+        sf.truncate(1200);
+        assert_eq!(sf.count_skipped(), 698 + 200);
+        assert_eq!(sf.debug()[1000], 200);
+        assert_eq!(sf.debug()[1199], 200);
+
+        sf.trim_trailing_skipped();
+        assert_eq!(Skipfield::len(&sf), 1000);
+        sf.skip_range(850..1000);
+        sf.trim_trailing_skipped();
+        assert_eq!(Skipfield::len(&sf), 850);
+        assert_eq!(sf.iter().next_back(), Some(849));
+    }
+
+    #[test]
+    fn test_runs_and_skipped_indices() {
+        let mut sf = LCJCSkipfield::new(12);
+        sf.skip_range(0..2);
+        sf.skip_range(5..8);
+        sf.skip(11);
+
+        assert_eq!(sf.skipped_runs().collect::<Vec<_>>(), vec![(0, 2), (5, 3), (11, 1)]);
+        assert_eq!(sf.active_runs().collect::<Vec<_>>(), vec![(2, 3), (8, 3)]);
+        assert_eq!(sf.skipped_indices().collect::<Vec<_>>(), vec![0, 1, 5, 6, 7, 11]);
+
+        let mut chained = LCJCSkipfield::new_chained(700);
+        chained.skip_range(100..650);
+        assert_eq!(chained.skipped_runs().collect::<Vec<_>>(), vec![(100, 550)]);
+        assert_eq!(chained.active_runs().collect::<Vec<_>>(), vec![(0, 100), (650, 50)]);
+        assert_eq!(LCJCSkipfield::new(0).active_runs().count(), 0);
+    }
+
+    fn runs_of(sf: &LCJCSkipfield) -> Vec<(usize, usize)> {
+        sf.skipped_runs().collect()
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let mut a = LCJCSkipfield::new(20);
+        a.skip_range(0..5);
+        a.skip_range(10..15);
+        let mut b = LCJCSkipfield::new(20);
+        b.skip_range(3..8);
+        b.skip_range(15..20);
+
+        assert_eq!(runs_of(&a.union(&b)), vec![(0, 8), (10, 10)]);
+        assert_eq!(runs_of(&a.intersect(&b)), vec![(3, 2)]);
+        assert_eq!(runs_of(&a.difference(&b)), vec![(0, 3), (10, 5)]);
+        assert_eq!(runs_of(&a.symmetric_difference(&b)), vec![(0, 3), (5, 3), (10, 10)]);
+        assert_eq!(runs_of(&a.complement()), vec![(5, 5), (15, 5)]);
+
+        let union = a.union(&b);
+        assert_eq!(union.debug()[0], 8);
+        assert_eq!(union.debug()[7], 8);
+        assert_eq!(union.iter().collect::<Vec<_>>(), vec![8, 9]);
+
+        a.symmetric_difference_with(&a.clone());
+        assert_eq!(a.count_skipped(), 0);
+        assert!(a.debug().iter().all(|&n| n == 0));
+    }
+
+    #[test]
+    fn test_set_algebra_chained_and_mixed_widths() {
+        let mut chained = LCJCSkipfield::new_chained(1000);
+        chained.skip_range(0..300);
+        let mut wide: LCJCSkipfield<u16> = LCJCSkipfield::with_len(1000);
+        wide.skip_range(200..900);
+
+        chained.union_with(&wide);
+        assert_eq!(runs_of(&chained), vec![(0, 900)]);
+        assert_eq!(chained.iter().next(), Some(900));
+
+        chained.invert();
+        assert_eq!(runs_of(&chained), vec![(900, 100)]);
+        assert_eq!(chained.count_active(), 900);
+    }
+
+    #[test]
+    #[should_panic(expected = "same length")]
+    fn test_set_algebra_length_mismatch() {
+        LCJCSkipfield::new(10).union_with(&LCJCSkipfield::new(11));
+    }
+
+    skipfield_conformance_tests!(LCJCSkipfield);
+    skipfield_conformance_tests!(conformance_u16: LCJCSkipfield<u16>);
+
+    #[test]
+    fn test_matches_model_with_long_runs() {
+        differential::check::<LCJCSkipfield<u16>>(&Config::default());
+    }
+
+    // a chained `u8` field behind the trait, so runs past 255 slots get split
+    // across nodes
+    struct Chained(LCJCSkipfield<u8>);
+
+    impl Skipfield for Chained {
+        fn new(len: usize) -> Self {
+            Chained(LCJCSkipfield::with_len_chained(len))
+        }
+
+        fn len(&self) -> usize {
+            Skipfield::len(&self.0)
+        }
+
+        fn skip(&mut self, idx: usize) {
+            Skipfield::skip(&mut self.0, idx)
+        }
+
+        fn unskip(&mut self, idx: usize) {
+            Skipfield::unskip(&mut self.0, idx)
+        }
+
+        fn is_skipped(&self, idx: usize) -> bool {
+            Skipfield::is_skipped(&self.0, idx)
+        }
+
+        fn first_active(&self) -> Option<usize> {
+            Skipfield::first_active(&self.0)
+        }
+
+        fn count_skipped(&self) -> usize {
+            Skipfield::count_skipped(&self.0)
+        }
+
+        fn skip_range(&mut self, range: Range<usize>) {
+            Skipfield::skip_range(&mut self.0, range)
+        }
+
+        fn unskip_range(&mut self, range: Range<usize>) {
+            Skipfield::unskip_range(&mut self.0, range)
+        }
+
+        fn is_range_skipped(&self, range: Range<usize>) -> bool {
+            Skipfield::is_range_skipped(&self.0, range)
+        }
+
+        fn any_active_in(&self, range: Range<usize>) -> bool {
+            Skipfield::any_active_in(&self.0, range)
+        }
+
+        fn count_active_in(&self, range: Range<usize>) -> usize {
+            Skipfield::count_active_in(&self.0, range)
+        }
+
+        fn active_indices(&self) -> impl Iterator<Item = usize> + '_ {
+            Skipfield::active_indices(&self.0)
+        }
+
+        fn skipped_indices(&self) -> impl Iterator<Item = usize> + '_ {
+            Skipfield::skipped_indices(&self.0)
+        }
+
+        fn active_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+            Skipfield::active_runs(&self.0)
+        }
+
+        fn skipped_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+            Skipfield::skipped_runs(&self.0)
+        }
+    }
+
+    skipfield_conformance_tests!(conformance_chained: Chained);
+
+    #[test]
+    fn test_chained_matches_model_with_long_runs() {
+        // the conformance suite stays under 256 slots, so only this reaches
+        // runs a single `u8` node can't hold
+        differential::check::<Chained>(&Config { max_len: 700, ..Config::default() });
+    }
+
+    // the trait impl only walks `LCJCSkipfieldIter` forwards, through
+    // `active_indices`. this also walks it from the back and answers
+    // `first_active` and `count_skipped` from it
+    struct ViaIter(LCJCSkipfield<u16>);
+
+    impl Skipfield for ViaIter {
+        fn new(len: usize) -> Self {
+            ViaIter(LCJCSkipfield::with_len(len))
+        }
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn skip(&mut self, idx: usize) {
+            self.0.skip(idx)
+        }
+
+        fn unskip(&mut self, idx: usize) {
+            self.0.unskip(idx)
+        }
+
+        fn is_skipped(&self, idx: usize) -> bool {
+            self.0.is_skipped(idx)
+        }
+
+        fn first_active(&self) -> Option<usize> {
+            self.0.iter().next()
+        }
+
+        fn count_skipped(&self) -> usize {
+            self.0.len() - self.0.iter().len()
+        }
+
+        fn active_indices(&self) -> impl Iterator<Item = usize> + '_ {
+            let forward: Vec<_> = self.0.iter().collect();
+            let mut backward: Vec<_> = self.0.iter().rev().collect();
+            backward.reverse();
+            assert_eq!(forward, backward, "iterating from the back disagrees");
+            forward.into_iter()
+        }
+    }
+
+    #[test]
+    fn test_iter_matches_model() {
+        differential::check::<ViaIter>(&Config::default());
+    }
+}
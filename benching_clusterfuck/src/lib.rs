@@ -1,3 +1,15 @@
+pub mod binary_format;
 pub mod bitmask_skipfield;
 pub mod bool_skipfield;
-pub mod lcjc_skipfield;
\ No newline at end of file
+pub mod compressed_skipfield;
+pub mod conversions;
+pub mod hcjc_skipfield;
+pub mod hive;
+pub mod lcjc_skipfield;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod summary;
+pub mod trace;
+
+// the scans live in the core crate so skipfields outside this one can share them
+pub use skipfield_core::simd;
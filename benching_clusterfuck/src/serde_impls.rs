@@ -0,0 +1,186 @@
+//! Compact serde forms. Bitmask and bool fields serialize as `{ len, words }`
+//! with one bit per slot, laid out as `BitmaskSkipfield::words`, so either can
+//! load what the other wrote. LCJC fields serialize as `{ len, chained, runs }`
+//! with one `(start, len)` pair per maximal skipped run. Their `len` is capped
+//! at `binary_format::DEFAULT_MAX_LEN`, since a short run list could otherwise
+//! ask for a field of any size.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use skipfield_core::Skipfield;
+
+use crate::binary_format::DEFAULT_MAX_LEN;
+use crate::bitmask_skipfield::BitmaskSkipfield;
+use crate::bool_skipfield::BoolSkipfield;
+use crate::lcjc_skipfield::{LCJCNode, LCJCSkipfield};
+use crate::simd;
+
+#[derive(Serialize, Deserialize)]
+struct WordsRepr<W> {
+    len: usize,
+    words: W,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RunsRepr<R> {
+    len: usize,
+    chained: bool,
+    runs: R,
+}
+
+impl Serialize for BitmaskSkipfield {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        WordsRepr { len: Skipfield::len(self), words: self.words() }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BitmaskSkipfield {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = WordsRepr::<Vec<u64>>::deserialize(deserializer)?;
+        check_words(repr.len, &repr.words).map_err(D::Error::custom)?;
+        Ok(BitmaskSkipfield::from_words(repr.words, repr.len))
+    }
+}
+
+impl Serialize for BoolSkipfield {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let len = Skipfield::len(self);
+        let mut words = simd::pack_bools(self.flags());
+        let tail_bits = len % 64;
+        if tail_bits > 0 {
+            words[len / 64] |= u64::MAX << tail_bits;
+        }
+        WordsRepr { len, words }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BoolSkipfield {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bitmask = BitmaskSkipfield::deserialize(deserializer)?;
+        let mut sf = BoolSkipfield::new(Skipfield::len(&bitmask));
+        for (start, len) in bitmask.skipped_runs() {
+            sf.skip_range(start..start + len);
+        }
+        Ok(sf)
+    }
+}
+
+impl<N: LCJCNode> Serialize for LCJCSkipfield<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let runs: Vec<(usize, usize)> = self.skipped_runs().collect();
+        RunsRepr { len: Skipfield::len(self), chained: self.is_chained(), runs }.serialize(serializer)
+    }
+}
+
+impl<'de, N: LCJCNode> Deserialize<'de> for LCJCSkipfield<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = RunsRepr::<Vec<(usize, usize)>>::deserialize(deserializer)?;
+        if repr.len > DEFAULT_MAX_LEN {
+            return Err(D::Error::custom(format!("field of {} slots is over the limit of {DEFAULT_MAX_LEN}", repr.len)));
+        }
+        // every run becomes one block (or one chain of blocks), so checking the
+        // runs is what keeps each block's start and end node in agreement
+        check_runs(repr.len, &repr.runs, (!repr.chained).then_some(N::MAX)).map_err(D::Error::custom)?;
+
+        let mut sf = if repr.chained {
+            LCJCSkipfield::<N>::with_len_chained(repr.len)
+        } else {
+            LCJCSkipfield::<N>::with_len(repr.len)
+        };
+        for (start, len) in repr.runs {
+            sf.skip_range(start..start + len);
+        }
+        Ok(sf)
+    }
+}
+
+fn check_words(len: usize, words: &[u64]) -> Result<(), String> {
+    let expected = len.div_ceil(64);
+    if words.len() != expected {
+        return Err(format!("{len} slots need {expected} words, got {}", words.len()));
+    }
+    let tail_bits = len % 64;
+    if tail_bits > 0 {
+        let padding = u64::MAX << tail_bits;
+        if words[expected - 1] & padding != padding {
+            return Err(format!("bits past slot {len} must be set"));
+        }
+    }
+    Ok(())
+}
+
+// runs must be non-empty, in bounds, ascending and separated by at least one
+// active slot, and fit `max_run` if there is one
+fn check_runs(len: usize, runs: &[(usize, usize)], max_run: Option<usize>) -> Result<(), String> {
+    let mut next_free = 0;
+    for &(start, run_len) in runs {
+        if run_len == 0 {
+            return Err(format!("empty run at {start}"));
+        }
+        if start < next_free {
+            return Err(format!("run at {start} overlaps or touches the previous run"));
+        }
+        let end = start.checked_add(run_len).filter(|&end| end <= len);
+        let Some(end) = end else {
+            return Err(format!("run ({start}, {run_len}) goes past the end of {len} slots"));
+        };
+        if let Some(max) = max_run.filter(|&max| run_len > max) {
+            return Err(format!("run of {run_len} slots doesn't fit a node of max {max}"));
+        }
+        next_free = end + 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitmask_and_bool_roundtrip() {
+        let mut bitmask = BitmaskSkipfield::new(130);
+        bitmask.skip_range(10..80);
+        bitmask.skip(129);
+
+        let json = serde_json::to_string(&bitmask).unwrap();
+        let back: BitmaskSkipfield = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.skipped_runs().collect::<Vec<_>>(), vec![(10, 70), (129, 1)]);
+
+        // both share a format
+        let as_bool: BoolSkipfield = serde_json::from_str(&json).unwrap();
+        assert_eq!(as_bool.count_skipped(), 71);
+        assert_eq!(serde_json::to_string(&as_bool).unwrap(), json);
+    }
+
+    #[test]
+    fn test_lcjc_roundtrip() {
+        let mut sf = LCJCSkipfield::new_chained(1000);
+        sf.skip_range(0..600);
+        sf.skip(999);
+
+        let json = serde_json::to_string(&sf).unwrap();
+        assert_eq!(json, r#"{"len":1000,"chained":true,"runs":[[0,600],[999,1]]}"#);
+        let back: LCJCSkipfield = serde_json::from_str(&json).unwrap();
+        assert!(back.is_chained());
+        assert_eq!(back.iter().collect::<Vec<_>>(), sf.iter().collect::<Vec<_>>());
+
+        let wide: LCJCSkipfield<u16> = serde_json::from_str(&json.replace("true", "false")).unwrap();
+        assert_eq!(wide.debug()[0], 600);
+        assert_eq!(wide.debug()[599], 600);
+    }
+
+    #[test]
+    fn test_rejects_broken_invariants() {
+        let err = |json: &str| serde_json::from_str::<BitmaskSkipfield>(json).err().unwrap().to_string();
+        assert!(err(r#"{"len":70,"words":[0]}"#).contains("need 2 words"));
+        assert!(err(r#"{"len":70,"words":[0,0]}"#).contains("must be set"));
+
+        let err = |json: &str| serde_json::from_str::<LCJCSkipfield>(json).err().unwrap().to_string();
+        assert!(err(r#"{"len":10,"chained":false,"runs":[[2,3],[5,1]]}"#).contains("touches"));
+        assert!(err(r#"{"len":10,"chained":false,"runs":[[8,3]]}"#).contains("past the end"));
+        assert!(err(r#"{"len":10,"chained":false,"runs":[[4,0]]}"#).contains("empty run"));
+        assert!(err(r#"{"len":300,"chained":false,"runs":[[0,256]]}"#).contains("doesn't fit"));
+        assert!(serde_json::from_str::<LCJCSkipfield>(r#"{"len":300,"chained":true,"runs":[[0,256]]}"#).is_ok());
+        assert!(err(r#"{"len":1000000000000,"chained":false,"runs":[]}"#).contains("over the limit"));
+    }
+}
@@ -0,0 +1,209 @@
+use std::ops::Range;
+
+/// Hierarchy of bitmasks over the chunks of a bitmask skipfield.
+///
+/// Bit `c` of the bottom level is set if chunk `c` has an active slot, and
+/// every level above has one bit per word of the level below, set if that word
+/// is non-zero. Finding the next chunk with an active slot touches one word
+/// per level, so it is O(log64 n) instead of a scan over every chunk.
+#[derive(Clone)]
+pub(crate) struct ChunkSummary {
+    levels: Vec<Vec<u64>>,
+    num_chunks: usize,
+}
+
+impl ChunkSummary {
+    pub(crate) fn new(num_chunks: usize, has_active: impl Fn(usize) -> bool) -> Self {
+        let mut summary = Self { levels: Vec::new(), num_chunks };
+        summary.rebuild(has_active);
+        summary
+    }
+
+    // words per level, bottom first
+    fn shape(num_chunks: usize) -> Vec<usize> {
+        let mut lens = vec![num_chunks.div_ceil(64)];
+        while lens[lens.len() - 1] > 1 {
+            lens.push(lens[lens.len() - 1].div_ceil(64));
+        }
+        lens
+    }
+
+    pub(crate) fn rebuild(&mut self, has_active: impl Fn(usize) -> bool) {
+        self.levels = Self::shape(self.num_chunks).into_iter().map(|len| vec![0; len]).collect();
+        self.refresh(0..self.num_chunks, has_active);
+    }
+
+    /// Follows the field growing or shrinking to `num_chunks` chunks.
+    /// `has_active` must already describe the new chunks.
+    pub(crate) fn resize(&mut self, num_chunks: usize, has_active: impl Fn(usize) -> bool) {
+        let old = self.num_chunks;
+        let shape = Self::shape(num_chunks);
+        self.num_chunks = num_chunks;
+        if shape.len() != self.levels.len() {
+            self.rebuild(has_active);
+            return;
+        }
+
+        // clear the bits past the new end of every level, then recompute the
+        // edge where old and new chunks meet
+        let mut bits = num_chunks;
+        for (level, &len) in self.levels.iter_mut().zip(&shape) {
+            level.resize(len, 0);
+            let tail_bits = bits % 64;
+            if tail_bits > 0 {
+                level[len - 1] &= (1u64 << tail_bits) - 1;
+            }
+            bits = len;
+        }
+        self.refresh(old.min(num_chunks).saturating_sub(1)..num_chunks, has_active);
+    }
+
+    /// Recomputes the bits of `chunks` and every summary word above them.
+    pub(crate) fn refresh(&mut self, chunks: Range<usize>, has_active: impl Fn(usize) -> bool) {
+        if chunks.start >= chunks.end {
+            return;
+        }
+        for chunk_i in chunks.clone() {
+            set_bit(&mut self.levels[0], chunk_i, has_active(chunk_i));
+        }
+
+        let (mut lo, mut hi) = (chunks.start / 64, (chunks.end - 1) / 64);
+        for k in 1..self.levels.len() {
+            let (below, above) = self.levels.split_at_mut(k);
+            for (word_i, &word) in below[k - 1].iter().enumerate().take(hi + 1).skip(lo) {
+                set_bit(&mut above[0], word_i, word != 0);
+            }
+            lo /= 64;
+            hi /= 64;
+        }
+    }
+
+    /// First chunk at or after `chunk_i` with an active slot.
+    pub(crate) fn next_from(&self, chunk_i: usize) -> Option<usize> {
+        if chunk_i >= self.num_chunks {
+            return None;
+        }
+
+        // climb until some level has a set bit at or after `pos` in its word
+        let mut pos = chunk_i;
+        let mut k = 0;
+        loop {
+            let level = &self.levels[k];
+            let word_i = pos / 64;
+            if word_i >= level.len() {
+                return None;
+            }
+            let word = level[word_i] & (u64::MAX << (pos % 64));
+            if word != 0 {
+                pos = word_i * 64 + word.trailing_zeros() as usize;
+                break;
+            }
+            if k + 1 == self.levels.len() {
+                return None;
+            }
+            pos = word_i + 1;
+            k += 1;
+        }
+
+        while k > 0 {
+            k -= 1;
+            pos = pos * 64 + self.levels[k][pos].trailing_zeros() as usize;
+        }
+        Some(pos)
+    }
+
+    /// Last chunk before `end` with an active slot.
+    pub(crate) fn prev_before(&self, end: usize) -> Option<usize> {
+        let end = end.min(self.num_chunks);
+        if end == 0 {
+            return None;
+        }
+
+        let mut pos = end - 1;
+        let mut k = 0;
+        loop {
+            let word_i = pos / 64;
+            let word = self.levels[k][word_i] & (u64::MAX >> (63 - pos % 64));
+            if word != 0 {
+                pos = word_i * 64 + 63 - word.leading_zeros() as usize;
+                break;
+            }
+            if word_i == 0 || k + 1 == self.levels.len() {
+                return None;
+            }
+            pos = word_i - 1;
+            k += 1;
+        }
+
+        while k > 0 {
+            k -= 1;
+            pos = pos * 64 + 63 - self.levels[k][pos].leading_zeros() as usize;
+        }
+        Some(pos)
+    }
+
+    /// Chunks with an active slot, in ascending order.
+    pub(crate) fn chunks(&self) -> impl Iterator<Item = usize> + '_ {
+        let mut next = 0;
+        std::iter::from_fn(move || {
+            let chunk_i = self.next_from(next)?;
+            next = chunk_i + 1;
+            Some(chunk_i)
+        })
+    }
+}
+
+#[inline]
+fn set_bit(words: &mut [u64], bit: usize, value: bool) {
+    if value {
+        words[bit / 64] |= 1u64 << (bit % 64);
+    } else {
+        words[bit / 64] &= !(1u64 << (bit % 64));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_and_prev_across_levels() {
+        let active = [0, 63, 64, 4095, 4096, 300_000];
+        let summary = ChunkSummary::new(300_001, |c| active.contains(&c));
+
+        assert_eq!(summary.chunks().collect::<Vec<_>>(), active);
+        assert_eq!(summary.next_from(65), Some(4095));
+        assert_eq!(summary.next_from(4097), Some(300_000));
+        assert_eq!(summary.next_from(300_001), None);
+        assert_eq!(summary.prev_before(300_000), Some(4096));
+        assert_eq!(summary.prev_before(4095), Some(64));
+        assert_eq!(summary.prev_before(0), None);
+        assert_eq!(summary.prev_before(usize::MAX), Some(300_000));
+    }
+
+    #[test]
+    fn test_refresh_and_resize() {
+        let mut active = vec![false; 5000];
+        let mut summary = ChunkSummary::new(5000, |c| active[c]);
+        assert_eq!(summary.next_from(0), None);
+
+        active[4999] = true;
+        summary.refresh(4999..5000, |c| active[c]);
+        assert_eq!(summary.next_from(0), Some(4999));
+        assert_eq!(summary.prev_before(5000), Some(4999));
+
+        summary.resize(4000, |c| active[c]);
+        assert_eq!(summary.next_from(0), None);
+
+        active[10] = true;
+        summary.resize(5000, |c| active[c]);
+        summary.refresh(10..11, |c| active[c]);
+        assert_eq!(summary.chunks().collect::<Vec<_>>(), vec![10, 4999]);
+
+        summary.resize(1, |c| active[c]);
+        assert_eq!(summary.next_from(0), None);
+        summary.resize(0, |c| active[c]);
+        assert_eq!(summary.next_from(0), None);
+        assert_eq!(summary.prev_before(1), None);
+    }
+}
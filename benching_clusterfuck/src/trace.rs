@@ -0,0 +1,525 @@
+//! Recorded skipfield workloads.
+//!
+//! A trace is a plain text file: a header line, the field length, then one
+//! operation per line. Blank lines and lines starting with `#` are ignored.
+//!
+//! ```text
+//! skipfield-trace 1
+//! len 1000
+//! skip 5
+//! skip_range 10 20
+//! first_active
+//! count_active_in 0 64
+//! ```
+//!
+//! [`Recorder`] wraps any skipfield and logs what is done to it, and
+//! [`Trace::replay`] runs a trace against a field, collecting the answer to
+//! every query so replays on different representations can be compared.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::Range;
+
+use skipfield_core::Skipfield;
+
+const HEADER: &str = "skipfield-trace 1";
+
+/// One recorded call. Ranges are written as `start end`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceOp {
+    Skip(usize),
+    Unskip(usize),
+    SkipRange(Range<usize>),
+    UnskipRange(Range<usize>),
+    IsSkipped(usize),
+    IsActive(usize),
+    FirstActive,
+    FirstSkipped,
+    CountSkipped,
+    CountActive,
+    IsRangeSkipped(Range<usize>),
+    CountActiveIn(Range<usize>),
+    AnyActiveIn(Range<usize>),
+    ActiveIndices,
+    SkippedIndices,
+    ActiveRuns,
+    SkippedRuns,
+}
+
+impl TraceOp {
+    fn name(&self) -> &'static str {
+        match self {
+            TraceOp::Skip(_) => "skip",
+            TraceOp::Unskip(_) => "unskip",
+            TraceOp::SkipRange(_) => "skip_range",
+            TraceOp::UnskipRange(_) => "unskip_range",
+            TraceOp::IsSkipped(_) => "is_skipped",
+            TraceOp::IsActive(_) => "is_active",
+            TraceOp::FirstActive => "first_active",
+            TraceOp::FirstSkipped => "first_skipped",
+            TraceOp::CountSkipped => "count_skipped",
+            TraceOp::CountActive => "count_active",
+            TraceOp::IsRangeSkipped(_) => "is_range_skipped",
+            TraceOp::CountActiveIn(_) => "count_active_in",
+            TraceOp::AnyActiveIn(_) => "any_active_in",
+            TraceOp::ActiveIndices => "active_indices",
+            TraceOp::SkippedIndices => "skipped_indices",
+            TraceOp::ActiveRuns => "active_runs",
+            TraceOp::SkippedRuns => "skipped_runs",
+        }
+    }
+
+    fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let args: Vec<usize> = words
+            .map(|w| w.parse().map_err(|_| format!("`{w}` is not an index")))
+            .collect::<Result<_, _>>()?;
+        let index = || match args[..] {
+            [i] => Ok(i),
+            _ => Err(format!("`{name}` takes one index")),
+        };
+        let range = || match args[..] {
+            [start, end] if start <= end => Ok(start..end),
+            [start, end] => Err(format!("range {start}..{end} is backwards")),
+            _ => Err(format!("`{name}` takes a start and an end")),
+        };
+        let none = |op| if args.is_empty() { Ok(op) } else { Err(format!("`{name}` takes no arguments")) };
+        match name {
+            "skip" => index().map(TraceOp::Skip),
+            "unskip" => index().map(TraceOp::Unskip),
+            "skip_range" => range().map(TraceOp::SkipRange),
+            "unskip_range" => range().map(TraceOp::UnskipRange),
+            "is_skipped" => index().map(TraceOp::IsSkipped),
+            "is_active" => index().map(TraceOp::IsActive),
+            "first_active" => none(TraceOp::FirstActive),
+            "first_skipped" => none(TraceOp::FirstSkipped),
+            "count_skipped" => none(TraceOp::CountSkipped),
+            "count_active" => none(TraceOp::CountActive),
+            "is_range_skipped" => range().map(TraceOp::IsRangeSkipped),
+            "count_active_in" => range().map(TraceOp::CountActiveIn),
+            "any_active_in" => range().map(TraceOp::AnyActiveIn),
+            "active_indices" => none(TraceOp::ActiveIndices),
+            "skipped_indices" => none(TraceOp::SkippedIndices),
+            "active_runs" => none(TraceOp::ActiveRuns),
+            "skipped_runs" => none(TraceOp::SkippedRuns),
+            _ => Err(format!("unknown operation `{name}`")),
+        }
+    }
+
+    // whether every index the op touches is inside a field of `len` slots
+    fn in_bounds(&self, len: usize) -> bool {
+        match self {
+            TraceOp::Skip(i) | TraceOp::Unskip(i) | TraceOp::IsSkipped(i) | TraceOp::IsActive(i) => *i < len,
+            TraceOp::SkipRange(r)
+            | TraceOp::UnskipRange(r)
+            | TraceOp::IsRangeSkipped(r)
+            | TraceOp::CountActiveIn(r)
+            | TraceOp::AnyActiveIn(r) => {
+                r.end <= len
+            }
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for TraceOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())?;
+        match self {
+            TraceOp::Skip(i) | TraceOp::Unskip(i) | TraceOp::IsSkipped(i) | TraceOp::IsActive(i) => write!(f, " {i}"),
+            TraceOp::SkipRange(r)
+            | TraceOp::UnskipRange(r)
+            | TraceOp::IsRangeSkipped(r)
+            | TraceOp::CountActiveIn(r)
+            | TraceOp::AnyActiveIn(r) => {
+                write!(f, " {} {}", r.start, r.end)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What a query returned during a replay. Index and run lists are kept as
+/// their length and a hash, so long traces don't hold every list in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+    Bool(bool),
+    Index(Option<usize>),
+    Count(usize),
+    Indices { count: usize, hash: u64 },
+}
+
+/// A parse failure, with the 1-based line it happened on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+/// A field length and the operations applied to a field of that length,
+/// starting with every slot active.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub len: usize,
+    pub ops: Vec<TraceOp>,
+}
+
+impl Trace {
+    pub fn new(len: usize) -> Self {
+        Self { len, ops: Vec::new() }
+    }
+
+    /// Parses a trace, checking every index against the field length.
+    pub fn parse(text: &str) -> Result<Self, TraceError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        let err = |line, msg: String| TraceError { line, msg };
+
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            Some((line, _)) => return Err(err(line, format!("expected `{HEADER}`"))),
+            None => return Err(err(0, "empty trace".to_string())),
+        }
+        let len = match lines.next() {
+            Some((line, text)) => text
+                .strip_prefix("len ")
+                .and_then(|n| n.trim().parse().ok())
+                .ok_or_else(|| err(line, "expected `len <slots>`".to_string()))?,
+            None => return Err(err(0, "missing `len` line".to_string())),
+        };
+
+        let mut trace = Trace::new(len);
+        for (line, text) in lines {
+            let op = TraceOp::parse(text).map_err(|msg| err(line, msg))?;
+            if !op.in_bounds(len) {
+                return Err(err(line, format!("`{op}` is out of bounds (len = {len})")));
+            }
+            trace.ops.push(op);
+        }
+        Ok(trace)
+    }
+
+    pub fn write_to(&self, mut out: impl Write) -> io::Result<()> {
+        writeln!(out, "{HEADER}")?;
+        writeln!(out, "len {}", self.len)?;
+        for op in &self.ops {
+            writeln!(out, "{op}")?;
+        }
+        Ok(())
+    }
+
+    /// Runs the trace against `sf`, which must be `len` slots long and all
+    /// active, returning the answer to every query in order.
+    pub fn replay<T: Skipfield>(&self, sf: &mut T) -> Vec<Answer> {
+        assert_eq!(sf.len(), self.len, "trace is for a field of {} slots", self.len);
+        assert_eq!(sf.count_skipped(), 0, "replays start from an all-active field");
+        let mut answers = Vec::new();
+        for op in &self.ops {
+            let answer = match op {
+                TraceOp::Skip(i) => {
+                    sf.skip(*i);
+                    continue;
+                }
+                TraceOp::Unskip(i) => {
+                    sf.unskip(*i);
+                    continue;
+                }
+                TraceOp::SkipRange(r) => {
+                    sf.skip_range(r.clone());
+                    continue;
+                }
+                TraceOp::UnskipRange(r) => {
+                    sf.unskip_range(r.clone());
+                    continue;
+                }
+                TraceOp::IsSkipped(i) => Answer::Bool(sf.is_skipped(*i)),
+                TraceOp::IsActive(i) => Answer::Bool(sf.is_active(*i)),
+                TraceOp::FirstActive => Answer::Index(sf.first_active()),
+                TraceOp::FirstSkipped => Answer::Index(sf.first_skipped()),
+                TraceOp::CountSkipped => Answer::Count(sf.count_skipped()),
+                TraceOp::CountActive => Answer::Count(sf.count_active()),
+                TraceOp::IsRangeSkipped(r) => Answer::Bool(sf.is_range_skipped(r.clone())),
+                TraceOp::CountActiveIn(r) => Answer::Count(sf.count_active_in(r.clone())),
+                TraceOp::AnyActiveIn(r) => Answer::Bool(sf.any_active_in(r.clone())),
+                TraceOp::ActiveIndices => indices_answer(sf.active_indices()),
+                TraceOp::SkippedIndices => indices_answer(sf.skipped_indices()),
+                TraceOp::ActiveRuns => runs_answer(sf.active_runs()),
+                TraceOp::SkippedRuns => runs_answer(sf.skipped_runs()),
+            };
+            answers.push(answer);
+        }
+        answers
+    }
+
+    /// Ops that return an answer, in the order `replay` answers them.
+    pub fn queries(&self) -> impl Iterator<Item = &TraceOp> {
+        self.ops.iter().filter(|op| {
+            !matches!(op, TraceOp::Skip(_) | TraceOp::Unskip(_) | TraceOp::SkipRange(_) | TraceOp::UnskipRange(_))
+        })
+    }
+}
+
+/// The skipped slots of `sf`, summed up like an index-list answer, for
+/// comparing where replays on different fields ended up.
+pub fn fingerprint<T: Skipfield>(sf: &T) -> Answer {
+    indices_answer(sf.skipped_indices())
+}
+
+fn indices_answer(indices: impl Iterator<Item = usize>) -> Answer {
+    let mut count = 0;
+    let hash = fnv1a(indices.inspect(|_| count += 1));
+    Answer::Indices { count, hash }
+}
+
+// counts the runs, hashing their starts and lengths in turn
+fn runs_answer(runs: impl Iterator<Item = (usize, usize)>) -> Answer {
+    let mut count = 0;
+    let hash = fnv1a(runs.inspect(|_| count += 1).flat_map(|(start, len)| [start, len]));
+    Answer::Indices { count, hash }
+}
+
+fn fnv1a(values: impl Iterator<Item = usize>) -> u64 {
+    values.fold(0xcbf2_9ce4_8422_2325u64, |hash, v| (hash ^ v as u64).wrapping_mul(0x100_0000_01b3))
+}
+
+/// Wraps a skipfield and records every call made through the `Skipfield`
+/// trait, except `len` and `is_empty`, which the trace header already
+/// answers. Every call goes straight to the wrapped field, so its own
+/// overrides run rather than the trait defaults. Slots already skipped when
+/// wrapping are recorded as ranges first, so the trace always starts from an
+/// all-active field.
+pub struct Recorder<T> {
+    inner: T,
+    // queries only get `&self`
+    ops: RefCell<Vec<TraceOp>>,
+}
+
+impl<T: Skipfield> Recorder<T> {
+    pub fn new(inner: T) -> Self {
+        let ops = inner.skipped_runs().map(|(start, len)| TraceOp::SkipRange(start..start + len)).collect();
+        Self { inner, ops: RefCell::new(ops) }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// The calls recorded so far.
+    pub fn trace(&self) -> Trace {
+        Trace { len: self.inner.len(), ops: self.ops.borrow().clone() }
+    }
+
+    pub fn into_trace(self) -> Trace {
+        Trace { len: self.inner.len(), ops: self.ops.into_inner() }
+    }
+
+    fn record(&self, op: TraceOp) {
+        self.ops.borrow_mut().push(op);
+    }
+}
+
+impl<T: Skipfield> Skipfield for Recorder<T> {
+    const MAX_SKIPPED_RUN: usize = T::MAX_SKIPPED_RUN;
+
+    fn new(len: usize) -> Self {
+        Recorder::new(T::new(len))
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    fn skip(&mut self, idx: usize) {
+        self.record(TraceOp::Skip(idx));
+        self.inner.skip(idx)
+    }
+
+    fn unskip(&mut self, idx: usize) {
+        self.record(TraceOp::Unskip(idx));
+        self.inner.unskip(idx)
+    }
+
+    fn is_skipped(&self, idx: usize) -> bool {
+        self.record(TraceOp::IsSkipped(idx));
+        self.inner.is_skipped(idx)
+    }
+
+    fn is_active(&self, idx: usize) -> bool {
+        self.record(TraceOp::IsActive(idx));
+        self.inner.is_active(idx)
+    }
+
+    fn first_active(&self) -> Option<usize> {
+        self.record(TraceOp::FirstActive);
+        self.inner.first_active()
+    }
+
+    fn first_skipped(&self) -> Option<usize> {
+        self.record(TraceOp::FirstSkipped);
+        self.inner.first_skipped()
+    }
+
+    fn count_skipped(&self) -> usize {
+        self.record(TraceOp::CountSkipped);
+        self.inner.count_skipped()
+    }
+
+    fn count_active(&self) -> usize {
+        self.record(TraceOp::CountActive);
+        self.inner.count_active()
+    }
+
+    fn skip_range(&mut self, range: Range<usize>) {
+        self.record(TraceOp::SkipRange(range.clone()));
+        self.inner.skip_range(range)
+    }
+
+    fn unskip_range(&mut self, range: Range<usize>) {
+        self.record(TraceOp::UnskipRange(range.clone()));
+        self.inner.unskip_range(range)
+    }
+
+    fn is_range_skipped(&self, range: Range<usize>) -> bool {
+        self.record(TraceOp::IsRangeSkipped(range.clone()));
+        self.inner.is_range_skipped(range)
+    }
+
+    fn any_active_in(&self, range: Range<usize>) -> bool {
+        self.record(TraceOp::AnyActiveIn(range.clone()));
+        self.inner.any_active_in(range)
+    }
+
+    fn count_active_in(&self, range: Range<usize>) -> usize {
+        self.record(TraceOp::CountActiveIn(range.clone()));
+        self.inner.count_active_in(range)
+    }
+
+    fn active_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.record(TraceOp::ActiveIndices);
+        self.inner.active_indices()
+    }
+
+    fn skipped_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.record(TraceOp::SkippedIndices);
+        self.inner.skipped_indices()
+    }
+
+    fn active_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.record(TraceOp::ActiveRuns);
+        self.inner.active_runs()
+    }
+
+    fn skipped_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.record(TraceOp::SkippedRuns);
+        self.inner.skipped_runs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitmask_skipfield::BitmaskSkipfield;
+    use crate::bool_skipfield::BoolSkipfield;
+    use crate::lcjc_skipfield::LCJCSkipfield;
+
+    fn workload(sf: &mut impl Skipfield) {
+        sf.skip_range(10..300);
+        sf.unskip(64);
+        sf.skip(999);
+        assert!(!sf.is_skipped(64));
+        assert_eq!(sf.first_active(), Some(0));
+        sf.skip_range(0..10);
+        assert_eq!(sf.first_active(), Some(64));
+        sf.count_active_in(0..128);
+        sf.any_active_in(10..64);
+        sf.active_indices().count();
+        sf.count_skipped();
+        assert!(sf.is_active(64) && sf.is_range_skipped(10..64));
+        assert_eq!(sf.first_skipped(), Some(0));
+        sf.active_runs().count();
+        sf.skipped_runs().count();
+    }
+
+    #[test]
+    fn test_record_write_parse_replay() {
+        let mut recorder = Recorder::new(BitmaskSkipfield::new(1000));
+        workload(&mut recorder);
+        let recorded_state = fingerprint(recorder.inner());
+        let trace = recorder.into_trace();
+        assert_eq!(trace.ops[0], TraceOp::SkipRange(10..300));
+        assert_eq!(trace.queries().count(), 12);
+        // each query is recorded as itself, not as the calls a default would make
+        assert_eq!(
+            trace.queries().skip(7).cloned().collect::<Vec<_>>(),
+            vec![
+                TraceOp::IsActive(64),
+                TraceOp::IsRangeSkipped(10..64),
+                TraceOp::FirstSkipped,
+                TraceOp::ActiveRuns,
+                TraceOp::SkippedRuns,
+            ]
+        );
+
+        let mut text = Vec::new();
+        trace.write_to(&mut text).unwrap();
+        let parsed = Trace::parse(std::str::from_utf8(&text).unwrap()).unwrap();
+        assert_eq!(parsed, trace);
+
+        let mut bools = BoolSkipfield::new(1000);
+        let answers = parsed.replay(&mut bools);
+        assert_eq!(answers.len(), 12);
+        assert_eq!(answers[1], Answer::Index(Some(0)));
+        assert_eq!(fingerprint(&bools), recorded_state);
+        assert_eq!(answers, parsed.replay(&mut BitmaskSkipfield::new(1000)));
+        let mut lcjc = LCJCSkipfield::new_chained(1000);
+        assert_eq!(answers, parsed.replay(&mut lcjc));
+        assert_eq!(fingerprint(&lcjc), recorded_state);
+    }
+
+    #[test]
+    fn test_wrapping_a_used_field_records_its_state() {
+        let mut sf = BoolSkipfield::new(100);
+        sf.skip_range(5..8);
+        sf.skip(50);
+        let trace = Recorder::new(sf).into_trace();
+        assert_eq!(trace.ops, vec![TraceOp::SkipRange(5..8), TraceOp::SkipRange(50..51)]);
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_line() {
+        let parse_err = |text: &str| Trace::parse(text).unwrap_err();
+        assert_eq!(parse_err("").line, 0);
+        assert_eq!(parse_err("trace\nlen 5").line, 1);
+        assert_eq!(parse_err("skipfield-trace 1\nlength 5").line, 2);
+
+        let err = parse_err("skipfield-trace 1\n# comment\nlen 10\n\nskip 3\nskip_range 4 2\n");
+        assert_eq!(err.line, 6);
+        assert!(err.msg.contains("backwards"));
+        assert_eq!(parse_err("skipfield-trace 1\nlen 10\nskip 10").line, 3);
+        assert_eq!(parse_err("skipfield-trace 1\nlen 10\nskip 18446744073709551615").line, 3);
+        assert_eq!(parse_err("skipfield-trace 1\nlen 10\nis_skipped 18446744073709551615").line, 3);
+        assert_eq!(parse_err("skipfield-trace 1\nlen 10\nfirst_active 1").line, 3);
+        assert_eq!(parse_err("skipfield-trace 1\nlen 10\nflip 1").to_string(), "line 3: unknown operation `flip`");
+    }
+}
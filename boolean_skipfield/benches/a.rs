@@ -1,5 +1,6 @@
-use criterion::{criterion_group, criterion_main, Criterion};
-use boolean_skipfield::BoolSkipfield;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const N: usize = 1_000_000;
 
 fn bench_count_skipped(c: &mut Criterion) {
     c.bench_function("count_skipped 100K bools", |b| {
@@ -8,7 +9,35 @@ fn bench_count_skipped(c: &mut Criterion) {
             let _ = sf.count_skipped();
         });
     });
+
+    // every third slot skipped, against the plain loop count_skipped used to be
+    let mut sf = boolean_skipfield::BoolSkipfield::new(N);
+    let mut flags = vec![false; N];
+    for i in (0..N).step_by(3) {
+        sf.skip(i);
+        flags[i] = true;
+    }
+    c.bench_function("count_skipped 1M bools scalar", |b| {
+        b.iter(|| black_box(black_box(&flags).iter().filter(|&&bit| bit).count()));
+    });
+    c.bench_function("count_skipped 1M bools", |b| {
+        b.iter(|| black_box(sf.count_skipped()));
+    });
+}
+
+fn bench_first_active(c: &mut Criterion) {
+    // only the last slot is active, so the whole field gets scanned
+    let mut sf = boolean_skipfield::BoolSkipfield::new(N);
+    let mut flags = vec![true; N];
+    sf.skip_range(0..N - 1);
+    flags[N - 1] = false;
+    c.bench_function("first_active 1M bools scalar", |b| {
+        b.iter(|| black_box(black_box(&flags).iter().position(|&bit| !bit)));
+    });
+    c.bench_function("first_active 1M bools", |b| {
+        b.iter(|| black_box(sf.first_active()));
+    });
 }
 
-criterion_group!(benches, bench_count_skipped);
+criterion_group!(benches, bench_count_skipped, bench_first_active);
 criterion_main!(benches);
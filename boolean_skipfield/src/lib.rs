@@ -1,3 +1,7 @@
+use std::ops::Range;
+
+use skipfield_core::{simd, Skipfield};
+
 pub struct BoolSkipfield {
     flags: Vec<bool>,
 }
@@ -21,14 +25,16 @@ impl BoolSkipfield {
         self.flags[index]
     }
 
+    pub fn skip_range(&mut self, range: Range<usize>) {
+        self.flags[range].fill(true);
+    }
+
+    pub fn unskip_range(&mut self, range: Range<usize>) {
+        self.flags[range].fill(false);
+    }
+
     pub fn count_skipped(&self) -> usize {
-        let mut count = 0;
-        for &bit in &self.flags {
-            if bit {
-                count += 1;
-            }
-        }
-        count
+        simd::count_true(&self.flags)
     }
 
     pub fn count_active(&self) -> usize {
@@ -36,11 +42,63 @@ impl BoolSkipfield {
     }
 
     pub fn first_active(&self) -> Option<usize> {
-        for (i, &bit) in self.flags.iter().enumerate() {
-            if !bit {
-                return Some(i);
-            }
-        }
-        None
+        simd::first_false(&self.flags)
+    }
+}
+
+impl Skipfield for BoolSkipfield {
+    fn new(len: usize) -> Self {
+        BoolSkipfield::new(len)
+    }
+
+    fn len(&self) -> usize {
+        self.flags.len()
+    }
+
+    fn skip(&mut self, idx: usize) {
+        BoolSkipfield::skip(self, idx)
+    }
+
+    fn unskip(&mut self, idx: usize) {
+        BoolSkipfield::unskip(self, idx)
     }
+
+    fn is_skipped(&self, idx: usize) -> bool {
+        BoolSkipfield::is_skipped(self, idx)
+    }
+
+    fn skip_range(&mut self, range: Range<usize>) {
+        BoolSkipfield::skip_range(self, range)
+    }
+
+    fn unskip_range(&mut self, range: Range<usize>) {
+        BoolSkipfield::unskip_range(self, range)
+    }
+
+    fn count_active_in(&self, range: Range<usize>) -> usize {
+        range.len() - simd::count_true(&self.flags[range])
+    }
+
+    fn first_active(&self) -> Option<usize> {
+        BoolSkipfield::first_active(self)
+    }
+
+    fn count_skipped(&self) -> usize {
+        BoolSkipfield::count_skipped(self)
+    }
+
+    fn active_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.flags
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &bit)| if !bit { Some(i) } else { None })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use skipfield_core::skipfield_conformance_tests;
+
+    skipfield_conformance_tests!(BoolSkipfield);
 }
@@ -0,0 +1,466 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use skipfield_core::Skipfield;
+
+use crate::{snapshot_from_words, thread_start, words_from_snapshot};
+
+/// Lock-free skipfield storing one bit per slot in `AtomicU64` words.
+///
+/// A set bit means skipped. The bits past `len` in the last word stay set so
+/// whole-word queries never see them as active.
+pub struct LockLessBitmaskSkipfield {
+    chunks: Vec<AtomicU64>,
+    len: usize,
+}
+
+impl LockLessBitmaskSkipfield {
+    /// Creates a field of `size` slots, all of them skipped.
+    pub fn new(size: usize) -> Self {
+        Self::filled(size, u64::MAX)
+    }
+
+    fn filled(size: usize, word: u64) -> Self {
+        let num_chunks = size.div_ceil(64);
+        let chunks = (0..num_chunks)
+            .map(|chunk_i| AtomicU64::new(word | !valid_bits(size, chunk_i)))
+            .collect();
+        Self { chunks, len: size }
+    }
+
+    /// Builds a field from raw words, bit `i % 64` of word `i / 64` set if slot
+    /// `i` is skipped. Bits past `len` are ignored.
+    pub fn from_words(words: Vec<u64>, len: usize) -> Self {
+        assert_eq!(words.len(), len.div_ceil(64), "{len} slots need {} words", len.div_ceil(64));
+        let chunks = words
+            .into_iter()
+            .enumerate()
+            .map(|(chunk_i, word)| AtomicU64::new(word | !valid_bits(len, chunk_i)))
+            .collect();
+        Self { chunks, len }
+    }
+
+    /// Copies out the raw words, laid out as `from_words` takes them. Bits
+    /// past `len` are always set.
+    ///
+    /// Each word is read with a single atomic load, so the 64 slots of a word
+    /// are always consistent with each other and a write that finished before
+    /// the call started is always seen. Different words are read at different
+    /// moments, so with writers running the copy as a whole may match no
+    /// single moment. Quiesce the writers first if it must.
+    pub fn load_words(&self) -> Vec<u64> {
+        self.chunks.iter().map(|chunk| chunk.load(Ordering::Acquire)).collect()
+    }
+
+    /// Seeds a field with the state of a single-threaded one.
+    pub fn from_snapshot(snapshot: &impl Skipfield) -> Self {
+        Self::from_words(words_from_snapshot(snapshot), snapshot.len())
+    }
+
+    /// Copies the field into a single-threaded skipfield, e.g.
+    /// `let snap: BitmaskSkipfield = sf.snapshot();`.
+    ///
+    /// The field is read once with `load_words`, so each word of the snapshot
+    /// is consistent and the snapshot as a whole may mix words from different
+    /// moments unless the writers are quiesced.
+    pub fn snapshot<S: Skipfield>(&self) -> S {
+        snapshot_from_words(&self.load_words(), self.len)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Marks `idx` active. Returns true if it was skipped before.
+    pub fn unskip(&self, idx: usize) -> bool {
+        let (chunk_i, mask) = self.bit(idx);
+        self.chunks[chunk_i].fetch_and(!mask, Ordering::AcqRel) & mask != 0
+    }
+
+    /// Marks `idx` skipped. Returns true if it was active before.
+    pub fn skip(&self, idx: usize) -> bool {
+        let (chunk_i, mask) = self.bit(idx);
+        self.chunks[chunk_i].fetch_or(mask, Ordering::AcqRel) & mask == 0
+    }
+
+    pub fn is_active(&self, idx: usize) -> bool {
+        let (chunk_i, mask) = self.bit(idx);
+        self.chunks[chunk_i].load(Ordering::Acquire) & mask == 0
+    }
+
+    /// Finds a skipped slot, marks it active and returns it. `None` once every
+    /// slot is active.
+    pub fn try_claim(&self) -> Option<usize> {
+        self.claim_n(1).pop()
+    }
+
+    /// Claims up to `k` skipped slots, taking as many as it can from each word
+    /// with a single CAS. Returns fewer if the field runs out.
+    pub fn claim_n(&self, k: usize) -> Vec<usize> {
+        let num_chunks = self.chunks.len();
+        let mut claimed = Vec::with_capacity(k.min(self.len));
+        if num_chunks == 0 {
+            return claimed;
+        }
+        let start = thread_start(num_chunks);
+        for chunk_i in (start..num_chunks).chain(0..start) {
+            if claimed.len() == k {
+                break;
+            }
+            let mut taken = self.claim_in_chunk(chunk_i, k - claimed.len());
+            while taken != 0 {
+                claimed.push(chunk_i * 64 + taken.trailing_zeros() as usize);
+                taken &= taken - 1;
+            }
+        }
+        claimed
+    }
+
+    /// Gives a claimed slot back. Returns false if it wasn't active.
+    pub fn release(&self, idx: usize) -> bool {
+        self.skip(idx)
+    }
+
+    // clears up to `max` of the lowest skipped bits in a chunk, retrying until
+    // the CAS wins or no skipped bit is left. returns the bits it cleared
+    fn claim_in_chunk(&self, chunk_i: usize, max: usize) -> u64 {
+        let chunk = &self.chunks[chunk_i];
+        let valid = valid_bits(self.len, chunk_i);
+        let mut current = chunk.load(Ordering::Relaxed);
+        loop {
+            let mut free = current & valid;
+            let mut take = 0;
+            for _ in 0..max {
+                if free == 0 {
+                    break;
+                }
+                take |= free & free.wrapping_neg();
+                free &= free - 1;
+            }
+            if take == 0 {
+                return 0;
+            }
+            match chunk.compare_exchange_weak(current, current & !take, Ordering::AcqRel, Ordering::Relaxed) {
+                Ok(_) => return take,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Each word is read atomically, but words are read one after another, so
+    /// under concurrent writes the total may mix states from different moments.
+    pub fn count_active(&self) -> usize {
+        (0..self.chunks.len())
+            .map(|chunk_i| self.active_word(chunk_i).count_ones() as usize)
+            .sum()
+    }
+
+    pub fn first_active(&self) -> Option<usize> {
+        (0..self.chunks.len()).find_map(|chunk_i| {
+            let word = self.active_word(chunk_i);
+            (word != 0).then(|| chunk_i * 64 + word.trailing_zeros() as usize)
+        })
+    }
+
+    pub fn alive_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.chunks.len()).flat_map(move |chunk_i| {
+            let mut word = self.active_word(chunk_i);
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let tz = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(chunk_i * 64 + tz)
+            })
+        })
+    }
+
+    #[inline]
+    fn bit(&self, idx: usize) -> (usize, u64) {
+        assert!(idx < self.len, "index {idx} out of bounds (len = {})", self.len);
+        (idx / 64, 1u64 << (idx % 64))
+    }
+
+    #[inline]
+    fn active_word(&self, chunk_i: usize) -> u64 {
+        !self.chunks[chunk_i].load(Ordering::Acquire) & valid_bits(self.len, chunk_i)
+    }
+}
+
+// bits of a chunk that lie inside the field
+#[inline]
+fn valid_bits(len: usize, chunk_i: usize) -> u64 {
+    let valid = len - chunk_i * 64;
+    if valid < 64 {
+        (1u64 << valid) - 1
+    } else {
+        u64::MAX
+    }
+}
+
+// like `LockLessBoolSkipfield`, the shared trait starts every slot active
+impl Skipfield for LockLessBitmaskSkipfield {
+    fn new(len: usize) -> Self {
+        Self::filled(len, 0)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn skip(&mut self, idx: usize) {
+        LockLessBitmaskSkipfield::skip(self, idx);
+    }
+
+    fn unskip(&mut self, idx: usize) {
+        LockLessBitmaskSkipfield::unskip(self, idx);
+    }
+
+    fn is_skipped(&self, idx: usize) -> bool {
+        !LockLessBitmaskSkipfield::is_active(self, idx)
+    }
+
+    fn first_active(&self) -> Option<usize> {
+        LockLessBitmaskSkipfield::first_active(self)
+    }
+
+    fn count_skipped(&self) -> usize {
+        self.len - LockLessBitmaskSkipfield::count_active(self)
+    }
+
+    fn count_active(&self) -> usize {
+        LockLessBitmaskSkipfield::count_active(self)
+    }
+
+    fn active_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.alive_indices()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use skipfield_core::skipfield_conformance_tests;
+    use std::thread;
+    use std::sync::Arc;
+
+    #[test]
+    fn concurrent_skip_unskip_no_overlap() {
+        const SIZE: usize = 1000;
+        const THREADS: usize = 10;
+
+        let skipfield = Arc::new(LockLessBitmaskSkipfield::new(SIZE));
+        let mut handles = Vec::new();
+        for t in 0..THREADS {
+            let sf = Arc::clone(&skipfield);
+            handles.push(thread::spawn(move || {
+                let start = t * (SIZE / THREADS);
+                let end = start + (SIZE / THREADS);
+                for i in start..end {
+                    sf.unskip(i);
+                }
+            }));
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        for i in 0..SIZE {
+            assert!(skipfield.is_active(i), "Slot {} was not active!", i);
+        }
+        assert_eq!(skipfield.count_active(), SIZE);
+    }
+
+    #[test]
+    fn concurrent_unskip_shared_words() {
+        const SIZE: usize = 1000;
+        const THREADS: usize = 8;
+
+        // every thread writes every word, so lost updates would show up here
+        let skipfield = Arc::new(LockLessBitmaskSkipfield::new(SIZE));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let sf = Arc::clone(&skipfield);
+                thread::spawn(move || {
+                    (t..SIZE).step_by(THREADS).filter(|&i| sf.unskip(i)).count()
+                })
+            })
+            .collect();
+
+        let flipped: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(flipped, SIZE);
+        assert_eq!(skipfield.count_active(), SIZE);
+        assert_eq!(skipfield.alive_indices().count(), SIZE);
+    }
+
+    #[test]
+    fn test_concurrent_erase_spares_neighbours() {
+        const SIZE: usize = 100000;
+        const ERASE_EVERY: usize = 2;
+
+        let skipfield = Arc::new(LockLessBitmaskSkipfield::new(SIZE));
+
+        // original state of skipfield. all are unerased
+        for i in 0..SIZE {
+            skipfield.unskip(i);
+        }
+
+        // writer thread erasing every even indexed element starting from the first element
+        let sf = Arc::clone(&skipfield);
+        let eraser = thread::spawn(move || {
+            for i in (0..SIZE).step_by(ERASE_EVERY) {
+                sf.skip(i);
+            }
+        });
+
+        // reader thread reading from the last element, racing the writer. odd
+        // slots share every word with slots being erased, so a write that
+        // clobbered its neighbours would show up here whatever the interleaving
+        let sf2 = Arc::clone(&skipfield);
+        let checker = thread::spawn(move || {
+            for i in (1..SIZE).rev().step_by(ERASE_EVERY) {
+                assert!(sf2.is_active(i), "Slot {} was touched by the eraser!", i);
+            }
+        });
+
+        eraser.join().unwrap();
+        checker.join().unwrap();
+
+        // validate
+        for i in (0..SIZE).step_by(ERASE_EVERY) {
+            assert!(!skipfield.is_active(i), "Slot {} should have been erased!", i);
+            assert!(skipfield.is_active(i+1), "Slot {} should still be alive!", i);
+        }
+        assert_eq!(skipfield.count_active(), SIZE / 2);
+    }
+
+    #[test]
+    fn test_previous_state_and_queries() {
+        let sf = LockLessBitmaskSkipfield::new(130);
+        assert_eq!(sf.count_active(), 0);
+        assert_eq!(sf.first_active(), None);
+
+        assert!(sf.unskip(129));
+        assert!(!sf.unskip(129));
+        assert!(sf.unskip(64));
+        assert_eq!(sf.first_active(), Some(64));
+        assert_eq!(sf.count_active(), 2);
+        assert_eq!(sf.alive_indices().collect::<Vec<_>>(), vec![64, 129]);
+
+        assert!(sf.skip(64));
+        assert!(!sf.skip(64));
+        assert_eq!(sf.first_active(), Some(129));
+    }
+
+    #[test]
+    fn test_skipfield_trait_starts_active() {
+        let mut sf = <LockLessBitmaskSkipfield as Skipfield>::new(70);
+        assert_eq!(Skipfield::count_active(&sf), 70);
+        assert_eq!(Skipfield::first_active(&sf), Some(0));
+
+        Skipfield::skip(&mut sf, 0);
+        Skipfield::skip(&mut sf, 69);
+        assert!(Skipfield::is_skipped(&sf, 0));
+        assert_eq!(Skipfield::first_active(&sf), Some(1));
+        assert_eq!(Skipfield::count_skipped(&sf), 2);
+        assert_eq!(Skipfield::active_indices(&sf).last(), Some(68));
+        assert!(<LockLessBitmaskSkipfield as Skipfield>::new(0).first_active().is_none());
+    }
+
+    #[test]
+    fn test_claim_and_release() {
+        let sf = LockLessBitmaskSkipfield::new(70);
+        let claimed = sf.claim_n(66);
+        assert_eq!(claimed.len(), 66);
+        assert!(claimed.iter().all(|&i| i < 70 && sf.is_active(i)));
+
+        assert_eq!(sf.claim_n(10).len(), 4);
+        assert_eq!(sf.try_claim(), None);
+        assert_eq!(sf.count_active(), 70);
+
+        assert!(sf.release(65));
+        assert!(!sf.release(65));
+        assert_eq!(sf.try_claim(), Some(65));
+        assert_eq!(LockLessBitmaskSkipfield::new(0).claim_n(3), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_claim_n_huge_k() {
+        // `k` only bounds the claim, it must not size the allocation
+        let sf = LockLessBitmaskSkipfield::new(100);
+        assert_eq!(sf.claim_n(usize::MAX).len(), 100);
+        assert_eq!(sf.claim_n(usize::MAX), Vec::<usize>::new());
+        assert_eq!(LockLessBitmaskSkipfield::new(0).claim_n(usize::MAX), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn concurrent_claims_are_unique() {
+        const SIZE: usize = 1000;
+        const THREADS: usize = 8;
+
+        let skipfield = Arc::new(LockLessBitmaskSkipfield::new(SIZE));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let sf = Arc::clone(&skipfield);
+                thread::spawn(move || {
+                    let mut mine = Vec::new();
+                    while let Some(i) = sf.try_claim() {
+                        mine.push(i);
+                        mine.extend(sf.claim_n(5));
+                    }
+                    mine
+                })
+            })
+            .collect();
+
+        let mut all: Vec<usize> = handles.into_iter().flat_map(|h| h.join().unwrap()).collect();
+        all.sort_unstable();
+        assert_eq!(all, (0..SIZE).collect::<Vec<_>>());
+        assert_eq!(skipfield.first_active(), Some(0));
+    }
+
+    #[test]
+    fn test_words_roundtrip() {
+        let sf = LockLessBitmaskSkipfield::new(130);
+        for i in (0..130).step_by(3) {
+            sf.unskip(i);
+        }
+
+        let words = sf.load_words();
+        assert_eq!(words.len(), 3);
+        assert_eq!(words[2] >> 2, u64::MAX >> 2);
+        let seeded = LockLessBitmaskSkipfield::from_words(words, 130);
+        assert_eq!(seeded.alive_indices().collect::<Vec<_>>(), sf.alive_indices().collect::<Vec<_>>());
+        assert_eq!(seeded.claim_n(200).len(), 130 - 44);
+
+        // bits past `len` come back set whatever was passed in
+        let seeded = LockLessBitmaskSkipfield::from_words(vec![0, 0], 70);
+        assert_eq!(seeded.count_active(), 70);
+        assert_eq!(seeded.load_words()[1], u64::MAX << 6);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_roundtrip() {
+        let sf = LockLessBitmaskSkipfield::new(130);
+        for i in (0..130).step_by(7) {
+            sf.unskip(i);
+        }
+
+        let json = serde_json::to_string(&sf).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            serde_json::json!({ "len": 130, "words": sf.load_words() })
+        );
+        let back: LockLessBitmaskSkipfield = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.alive_indices().collect::<Vec<_>>(), sf.alive_indices().collect::<Vec<_>>());
+        assert!(serde_json::from_str::<LockLessBitmaskSkipfield>(r#"{"len":70,"words":[0,0]}"#).is_err());
+    }
+
+    skipfield_conformance_tests!(LockLessBitmaskSkipfield);
+}
@@ -1,4 +1,15 @@
+use std::cell::Cell;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use skipfield_core::Skipfield;
+
+pub mod bitmask_skipfield;
+#[cfg(feature = "serde")]
+mod serde_impls;
+
+pub use bitmask_skipfield::LockLessBitmaskSkipfield;
 
 pub struct LockLessBoolSkipfield {
     flags: Vec<AtomicBool>,
@@ -11,6 +22,56 @@ impl LockLessBoolSkipfield {
         }
     }
 
+    /// Builds a field from raw words, bit `i % 64` of word `i / 64` set if slot
+    /// `i` is skipped. Bits past `len` are ignored.
+    pub fn from_words(words: Vec<u64>, len: usize) -> Self {
+        assert_eq!(words.len(), len.div_ceil(64), "{len} slots need {} words", len.div_ceil(64));
+        Self {
+            flags: (0..len).map(|i| AtomicBool::new(words[i / 64] & (1 << (i % 64)) == 0)).collect(),
+        }
+    }
+
+    /// Packs the field into raw words, laid out as `from_words` takes them.
+    /// Bits past `len` are always set.
+    ///
+    /// Every slot is read with a single atomic load, so each slot holds a
+    /// state it really had at some point during the call, and a write that
+    /// finished before the call started is always seen. Slots are read one
+    /// after another though, so with writers running the copy as a whole may
+    /// match no single moment: a writer that skips slot 10 and then slot 2 can
+    /// show up with slot 10 skipped but slot 2 not. Quiesce the writers first
+    /// if the whole field must be consistent.
+    pub fn load_words(&self) -> Vec<u64> {
+        let len = self.flags.len();
+        let mut words = vec![0u64; len.div_ceil(64)];
+        for (i, flag) in self.flags.iter().enumerate() {
+            if !flag.load(Ordering::Acquire) {
+                words[i / 64] |= 1 << (i % 64);
+            }
+        }
+        let tail_bits = len % 64;
+        if tail_bits > 0 {
+            words[len / 64] |= u64::MAX << tail_bits;
+        }
+        words
+    }
+
+    /// Seeds a field with the state of a single-threaded one.
+    pub fn from_snapshot(snapshot: &impl Skipfield) -> Self {
+        Self::from_words(words_from_snapshot(snapshot), snapshot.len())
+    }
+
+    /// Copies the field into a single-threaded skipfield, e.g.
+    /// `let snap: BitmaskSkipfield = sf.snapshot();`.
+    ///
+    /// The field is read once with `load_words`, so the snapshot carries the
+    /// same guarantees under concurrent writes: every slot holds a state it
+    /// really had during the call, but the snapshot as a whole may match no
+    /// single moment unless the writers are quiesced.
+    pub fn snapshot<S: Skipfield>(&self) -> S {
+        snapshot_from_words(&self.load_words(), self.flags.len())
+    }
+
     pub fn unskip(&self, idx: usize) -> bool {
         !self.flags[idx].swap(true, Ordering::AcqRel)
     }
@@ -23,6 +84,35 @@ impl LockLessBoolSkipfield {
         self.flags[idx].load(Ordering::Acquire)
     }
 
+    /// Finds a skipped slot, marks it active and returns it. `None` once every
+    /// slot is active.
+    pub fn try_claim(&self) -> Option<usize> {
+        self.claim_n(1).pop()
+    }
+
+    /// Claims up to `k` skipped slots. Returns fewer if the field runs out.
+    pub fn claim_n(&self, k: usize) -> Vec<usize> {
+        let len = self.flags.len();
+        if len == 0 || k == 0 {
+            return Vec::new();
+        }
+        let start = thread_start(len);
+        (start..len)
+            .chain(0..start)
+            .filter(|&i| {
+                let flag = &self.flags[i];
+                !flag.load(Ordering::Relaxed)
+                    && flag.compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed).is_ok()
+            })
+            .take(k)
+            .collect()
+    }
+
+    /// Gives a claimed slot back. Returns false if it wasn't active.
+    pub fn release(&self, idx: usize) -> bool {
+        self.skip(idx)
+    }
+
     pub fn alive_indices(&self) -> impl Iterator<Item = usize> + '_ {
         self.flags
             .iter()
@@ -32,9 +122,97 @@ impl LockLessBoolSkipfield {
     }
 }
 
+// where this thread starts scanning a field of `n` slots or words. threads
+// start at different points, so concurrent claims mostly touch different words
+pub(crate) fn thread_start(n: usize) -> usize {
+    thread_local! {
+        static SEED: Cell<Option<u64>> = const { Cell::new(None) };
+    }
+    let seed = SEED.with(|seed| {
+        *seed.get().get_or_insert_with(|| {
+            let mut hasher = DefaultHasher::new();
+            thread::current().id().hash(&mut hasher);
+            hasher.finish()
+        })
+    });
+    (seed % n as u64) as usize
+}
+
+// packs the skipped runs of any skipfield into words laid out as `from_words`
+// takes them
+pub(crate) fn words_from_snapshot(snapshot: &impl Skipfield) -> Vec<u64> {
+    let mut words = vec![0u64; snapshot.len().div_ceil(64)];
+    for (start, run_len) in snapshot.skipped_runs() {
+        for i in start..start + run_len {
+            words[i / 64] |= 1 << (i % 64);
+        }
+    }
+    words
+}
+
+// builds a field of type `S` from packed words, one `skip_range` per skipped run
+pub(crate) fn snapshot_from_words<S: Skipfield>(words: &[u64], len: usize) -> S {
+    let mut snapshot = S::new(len);
+    let mut run_start = None;
+    for i in 0..len {
+        let skipped = words[i / 64] & (1 << (i % 64)) != 0;
+        match (skipped, run_start) {
+            (true, None) => run_start = Some(i),
+            (false, Some(start)) => {
+                snapshot.skip_range(start..i);
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(start) = run_start {
+        snapshot.skip_range(start..len);
+    }
+    snapshot
+}
+
+// through the shared trait the field starts out with every slot active, like
+// every other skipfield, rather than all skipped as `LockLessBoolSkipfield::new`
+impl Skipfield for LockLessBoolSkipfield {
+    fn new(len: usize) -> Self {
+        Self {
+            flags: (0..len).map(|_| AtomicBool::new(true)).collect()
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.flags.len()
+    }
+
+    fn skip(&mut self, idx: usize) {
+        LockLessBoolSkipfield::skip(self, idx);
+    }
+
+    fn unskip(&mut self, idx: usize) {
+        LockLessBoolSkipfield::unskip(self, idx);
+    }
+
+    fn is_skipped(&self, idx: usize) -> bool {
+        !LockLessBoolSkipfield::is_active(self, idx)
+    }
+
+    fn first_active(&self) -> Option<usize> {
+        self.alive_indices().next()
+    }
+
+    fn count_skipped(&self) -> usize {
+        self.flags.len() - self.alive_indices().count()
+    }
+
+    fn active_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.alive_indices()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use skipfield_core::skipfield_conformance_tests;
     use std::thread;
     use std::sync::Arc;
 
@@ -112,4 +290,121 @@ mod tests {
             assert!(skipfield.is_active(i+1), "Slot {} should still be alive!", i);
         }
     }
+
+    #[test]
+    fn test_skipfield_trait_starts_active() {
+        let mut sf = <LockLessBoolSkipfield as Skipfield>::new(5);
+        assert_eq!(Skipfield::count_active(&sf), 5);
+        assert_eq!(Skipfield::first_active(&sf), Some(0));
+
+        Skipfield::skip(&mut sf, 0);
+        Skipfield::skip(&mut sf, 3);
+        assert!(Skipfield::is_skipped(&sf, 0));
+        assert_eq!(Skipfield::first_active(&sf), Some(1));
+        assert_eq!(Skipfield::count_skipped(&sf), 2);
+        assert_eq!(Skipfield::active_indices(&sf).collect::<Vec<_>>(), vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn test_claim_and_release() {
+        let sf = LockLessBoolSkipfield::new(10);
+        let claimed = sf.claim_n(4);
+        assert_eq!(claimed.len(), 4);
+        assert!(claimed.iter().all(|&i| sf.is_active(i)));
+
+        let rest = sf.claim_n(100);
+        assert_eq!(rest.len(), 6);
+        assert_eq!(sf.try_claim(), None);
+
+        assert!(sf.release(claimed[2]));
+        assert!(!sf.release(claimed[2]));
+        assert_eq!(sf.try_claim(), Some(claimed[2]));
+        assert_eq!(LockLessBoolSkipfield::new(0).try_claim(), None);
+    }
+
+    #[test]
+    fn test_claim_n_huge_k() {
+        let sf = LockLessBoolSkipfield::new(100);
+        assert_eq!(sf.claim_n(usize::MAX).len(), 100);
+        assert_eq!(sf.claim_n(usize::MAX), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn concurrent_claims_are_unique() {
+        const SIZE: usize = 1000;
+        const THREADS: usize = 8;
+
+        let skipfield = Arc::new(LockLessBoolSkipfield::new(SIZE));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let sf = Arc::clone(&skipfield);
+                thread::spawn(move || {
+                    let mut mine = Vec::new();
+                    while let Some(i) = sf.try_claim() {
+                        mine.push(i);
+                        mine.extend(sf.claim_n(3));
+                    }
+                    mine
+                })
+            })
+            .collect();
+
+        let mut all: Vec<usize> = handles.into_iter().flat_map(|h| h.join().unwrap()).collect();
+        all.sort_unstable();
+        assert_eq!(all, (0..SIZE).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_words_roundtrip() {
+        let sf = LockLessBoolSkipfield::new(100);
+        for i in (0..100).step_by(3) {
+            sf.unskip(i);
+        }
+
+        let words = sf.load_words();
+        assert_eq!(words.len(), 2);
+        assert_eq!(words[0] & 0b111, 0b110);
+        assert_eq!(words[1] >> 36, u64::MAX >> 36);
+        let seeded = LockLessBoolSkipfield::from_words(words, 100);
+        assert_eq!(seeded.alive_indices().collect::<Vec<_>>(), sf.alive_indices().collect::<Vec<_>>());
+        assert_eq!(LockLessBoolSkipfield::from_words(vec![0, 0], 70).alive_indices().count(), 70);
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let sf = LockLessBoolSkipfield::new(100);
+        for i in (0..100).step_by(3) {
+            sf.unskip(i);
+        }
+
+        // any skipfield can hold the snapshot, the lockless ones included
+        let snap: LockLessBitmaskSkipfield = sf.snapshot();
+        assert_eq!(snap.count_active(), 34);
+        assert_eq!(snap.alive_indices().collect::<Vec<_>>(), sf.alive_indices().collect::<Vec<_>>());
+        assert_eq!(Skipfield::skipped_runs(&snap).next(), Some((1, 2)));
+
+        let seeded = LockLessBoolSkipfield::from_snapshot(&snap);
+        assert_eq!(seeded.alive_indices().collect::<Vec<_>>(), sf.alive_indices().collect::<Vec<_>>());
+        assert!(LockLessBoolSkipfield::new(0).snapshot::<LockLessBitmaskSkipfield>().is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_roundtrip() {
+        let sf = LockLessBoolSkipfield::new(100);
+        for i in (0..100).step_by(7) {
+            sf.unskip(i);
+        }
+
+        let json = serde_json::to_string(&sf).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            serde_json::json!({ "len": 100, "words": sf.load_words() })
+        );
+        let back: LockLessBoolSkipfield = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.alive_indices().collect::<Vec<_>>(), sf.alive_indices().collect::<Vec<_>>());
+        assert!(serde_json::from_str::<LockLessBoolSkipfield>(r#"{"len":70,"words":[0,0]}"#).is_err());
+    }
+
+    skipfield_conformance_tests!(LockLessBoolSkipfield);
 }
@@ -0,0 +1,58 @@
+//! Both fields serialize as `{ len, words }` with one bit per slot, laid out
+//! as `load_words` returns them. That is the form the single-threaded bitmask
+//! skipfields use, so either side can load what the other wrote.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use skipfield_core::Skipfield;
+
+use crate::{LockLessBitmaskSkipfield, LockLessBoolSkipfield};
+
+#[derive(Serialize, Deserialize)]
+struct WordsRepr {
+    len: usize,
+    words: Vec<u64>,
+}
+
+impl Serialize for LockLessBoolSkipfield {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        WordsRepr { len: Skipfield::len(self), words: self.load_words() }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for LockLessBoolSkipfield {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = WordsRepr::deserialize(deserializer)?;
+        check_words(repr.len, &repr.words).map_err(D::Error::custom)?;
+        Ok(LockLessBoolSkipfield::from_words(repr.words, repr.len))
+    }
+}
+
+impl Serialize for LockLessBitmaskSkipfield {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        WordsRepr { len: self.len(), words: self.load_words() }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for LockLessBitmaskSkipfield {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = WordsRepr::deserialize(deserializer)?;
+        check_words(repr.len, &repr.words).map_err(D::Error::custom)?;
+        Ok(LockLessBitmaskSkipfield::from_words(repr.words, repr.len))
+    }
+}
+
+fn check_words(len: usize, words: &[u64]) -> Result<(), String> {
+    let expected = len.div_ceil(64);
+    if words.len() != expected {
+        return Err(format!("{len} slots need {expected} words, got {}", words.len()));
+    }
+    let tail_bits = len % 64;
+    if tail_bits > 0 {
+        let padding = u64::MAX << tail_bits;
+        if words[expected - 1] & padding != padding {
+            return Err(format!("bits past slot {len} must be set"));
+        }
+    }
+    Ok(())
+}
@@ -1,3 +1,5 @@
+use skipfield_core::Skipfield as SkipfieldTrait;
+
 pub struct Skipfield {
     chunks: Vec<u64>,
     len: usize,
@@ -5,13 +7,31 @@ pub struct Skipfield {
 
 impl Skipfield {
     pub fn new(len: usize) -> Self {
-        let num_chunks = (len+63)/64;
+        let num_chunks = len.div_ceil(64);
         Self {
             chunks: vec![0u64; num_chunks],
             len
         }
     }
 
+    /// Builds a field from raw words, bit `i % 64` of word `i / 64` set if slot
+    /// `i` is skipped. Bits past `len` are ignored.
+    pub fn from_words(mut chunks: Vec<u64>, len: usize) -> Self {
+        assert_eq!(chunks.len(), len.div_ceil(64), "{len} slots need {} words", len.div_ceil(64));
+        let tail_bits = len % 64;
+        if tail_bits > 0 {
+            let last = chunks.len() - 1;
+            chunks[last] &= (1u64 << tail_bits) - 1;
+        }
+        Self { chunks, len }
+    }
+
+    /// The raw words, laid out as `from_words` takes them. Bits past `len`
+    /// are always clear.
+    pub fn words(&self) -> &[u64] {
+        &self.chunks
+    }
+
     pub fn skip(&mut self, idx: usize) {
         let (chunk_idx, bit_idx) = Self::bit_pos(idx);
         self.chunks[chunk_idx] |= 1 << bit_idx;
@@ -19,28 +39,28 @@ impl Skipfield {
 
     pub fn unskip(&mut self, idx: usize) {
         let (chunk_idx, bit_idx) = Self::bit_pos(idx);
-        self.chunks[chunk_idx] &= 1 << bit_idx;
+        self.chunks[chunk_idx] &= !(1 << bit_idx);
     }
 
     pub fn is_skipped(&self, index: usize) -> bool {
         let (chunk_idx, bit_idx) = Self::bit_pos(index);
-        (self.chunks[chunk_idx] & (1 << bit_idx)) == 1
+        (self.chunks[chunk_idx] & (1 << bit_idx)) != 0
     }
 
     pub fn first_free(&self) -> Option<usize> {
         for (chunk_i, &chunk) in self.chunks.iter().enumerate() {
             let inv = !chunk;
             if inv != 0 {
-                return Some(chunk_i * 64 + inv.trailing_zeros() as usize);
+                // the bits past `len` are clear, so don't hand them out
+                let idx = chunk_i * 64 + inv.trailing_zeros() as usize;
+                return (idx < self.len).then_some(idx);
             }
         }
         None
     }
 
     pub fn count_skipped(&self) -> usize {
-        let total_bits = self.len;
-        let skipped = self.chunks.iter().map(|c| c.count_ones() as usize).sum::<usize>();
-        total_bits - skipped
+        self.chunks.iter().map(|c| c.count_ones() as usize).sum()
     }
 
     #[inline]
@@ -48,4 +68,57 @@ impl Skipfield {
         (index / 64, index % 64)
     }
 }
-            
+
+impl SkipfieldTrait for Skipfield {
+    fn new(len: usize) -> Self {
+        Skipfield::new(len)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn skip(&mut self, idx: usize) {
+        Skipfield::skip(self, idx)
+    }
+
+    fn unskip(&mut self, idx: usize) {
+        Skipfield::unskip(self, idx)
+    }
+
+    fn is_skipped(&self, idx: usize) -> bool {
+        Skipfield::is_skipped(self, idx)
+    }
+
+    fn first_active(&self) -> Option<usize> {
+        self.first_free()
+    }
+
+    fn count_skipped(&self) -> usize {
+        Skipfield::count_skipped(self)
+    }
+
+    fn active_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len).filter(move |&i| !Skipfield::is_skipped(self, i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use skipfield_core::skipfield_conformance_tests;
+
+    skipfield_conformance_tests!(Skipfield);
+
+    #[test]
+    fn test_count_skipped_counts_skipped_slots() {
+        let mut sf = Skipfield::new(100);
+        assert_eq!(sf.count_skipped(), 0);
+
+        sf.skip(3);
+        sf.skip(64);
+        sf.skip(99);
+        // used to report `len - skipped`, i.e. the active count
+        assert_eq!(sf.count_skipped(), 3);
+    }
+}
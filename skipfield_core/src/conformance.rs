@@ -0,0 +1,251 @@
+//! Conformance suite for [`Skipfield`] implementors.
+//!
+//! Each function checks one area of the contract and panics on the first
+//! violation. [`skipfield_conformance_tests!`](crate::skipfield_conformance_tests)
+//! turns the whole suite into `#[test]` functions for a type. Fields stay
+//! under 256 slots, so representations with narrow run lengths still fit.
+
+use std::ops::Range;
+
+use crate::differential::{self, Config};
+use crate::Skipfield;
+
+// lengths around the edges of 64-bit chunks
+const LENS: [usize; 10] = [0, 1, 2, 63, 64, 65, 127, 128, 129, 200];
+
+/// A new field has every slot active and reports it consistently.
+pub fn new_field_is_all_active<T: Skipfield>() {
+    for len in LENS {
+        let sf = T::new(len);
+        assert_eq!(sf.len(), len);
+        assert_eq!(sf.is_empty(), len == 0);
+        assert_eq!(sf.count_active(), len);
+        assert_eq!(sf.count_skipped(), 0);
+        assert_eq!(sf.first_active(), (len > 0).then_some(0));
+        assert!((0..len).all(|i| sf.is_active(i) && !sf.is_skipped(i)));
+        assert_eq!(sf.active_indices().collect::<Vec<_>>(), (0..len).collect::<Vec<_>>());
+        assert_eq!(sf.skipped_indices().count(), 0);
+        assert_eq!(sf.active_runs().collect::<Vec<_>>(), (len > 0).then_some((0, len)).into_iter().collect::<Vec<_>>());
+        assert_eq!(sf.skipped_runs().count(), 0);
+        check_consistency(&sf);
+    }
+}
+
+/// A zero-length field answers every query without touching a slot.
+pub fn empty_field<T: Skipfield>() {
+    let mut sf = T::new(0);
+    assert!(sf.is_empty());
+    assert_eq!(sf.first_active(), None);
+    assert_eq!(sf.count_skipped(), 0);
+    assert_eq!(sf.count_active(), 0);
+    assert_eq!(sf.active_indices().next(), None);
+    assert_eq!(sf.skipped_indices().next(), None);
+    assert_eq!(sf.active_runs().next(), None);
+    assert_eq!(sf.skipped_runs().next(), None);
+    assert!(sf.is_range_skipped(0..0));
+    assert!(!sf.any_active_in(0..0));
+    assert_eq!(sf.count_active_in(0..0), 0);
+
+    sf.skip_range(0..0);
+    sf.unskip_range(0..0);
+    assert!(sf.is_empty());
+}
+
+/// Skipping every slot, one at a time or as a range, leaves nothing active.
+pub fn fully_skipped_field<T: Skipfield>() {
+    for len in LENS.into_iter().filter(|&len| len > 0) {
+        let mut one_by_one = T::new(len);
+        for i in 0..len {
+            one_by_one.skip(i);
+        }
+        let mut ranged = T::new(len);
+        ranged.skip_range(0..len);
+
+        for sf in [&one_by_one, &ranged] {
+            assert_eq!(sf.first_active(), None);
+            assert_eq!(sf.count_skipped(), len);
+            assert_eq!(sf.count_active(), 0);
+            assert_eq!(sf.active_indices().next(), None);
+            assert_eq!(sf.skipped_runs().collect::<Vec<_>>(), vec![(0, len)]);
+            assert!(sf.is_range_skipped(0..len));
+            assert!(!sf.any_active_in(0..len));
+            check_consistency(sf);
+        }
+
+        // unskipping the last slot has to surface it, not a padding bit
+        ranged.unskip(len - 1);
+        assert_eq!(ranged.first_active(), Some(len - 1));
+        assert_eq!(ranged.count_active(), 1);
+        ranged.unskip_range(0..len);
+        assert_eq!(ranged.count_skipped(), 0);
+        check_consistency(&ranged);
+    }
+}
+
+/// Slots on either side of a chunk boundary are independent, and searches
+/// cross boundaries.
+pub fn chunk_boundaries<T: Skipfield>() {
+    let mut sf = T::new(200);
+    sf.skip(63);
+    sf.skip(64);
+    assert!(sf.is_skipped(63) && sf.is_skipped(64));
+    assert!(sf.is_active(62) && sf.is_active(65));
+    assert_eq!(sf.skipped_runs().collect::<Vec<_>>(), vec![(63, 2)]);
+
+    sf.unskip(63);
+    assert!(sf.is_active(63) && sf.is_skipped(64));
+    sf.unskip(64);
+    assert_eq!(sf.count_skipped(), 0);
+
+    for end in [63, 64, 65, 128, 129, 199] {
+        let mut sf = T::new(200);
+        sf.skip_range(0..end);
+        assert_eq!(sf.first_active(), Some(end));
+        assert_eq!(sf.count_active_in(0..end + 1), 1);
+        assert_eq!(sf.active_runs().next(), Some((end, 200 - end)));
+        check_consistency(&sf);
+    }
+
+    let mut sf = T::new(200);
+    sf.skip_range(60..130);
+    sf.unskip_range(63..65);
+    sf.unskip_range(127..129);
+    assert_eq!(sf.skipped_runs().collect::<Vec<_>>(), runs_of(&[60..63, 65..127, 129..130]));
+    assert!(sf.is_range_skipped(65..127));
+    assert!(sf.any_active_in(62..66));
+    assert_eq!(sf.count_active_in(60..130), 4);
+    check_consistency(&sf);
+}
+
+/// The slots past `len` in a partly used last chunk never leak into counts,
+/// searches or iterators.
+pub fn tail_bits<T: Skipfield>() {
+    for len in [1, 2, 63, 65, 100, 127, 129, 200] {
+        let mut sf = T::new(len);
+        sf.skip_range(0..len - 1);
+        assert_eq!(sf.first_active(), Some(len - 1));
+        assert_eq!(sf.count_skipped(), len - 1);
+        assert_eq!(sf.active_indices().collect::<Vec<_>>(), vec![len - 1]);
+
+        sf.skip(len - 1);
+        assert_eq!(sf.first_active(), None);
+        assert_eq!(sf.count_skipped(), len);
+        assert_eq!(sf.active_indices().count(), 0);
+        assert_eq!(sf.skipped_indices().last(), Some(len - 1));
+
+        sf.unskip_range(0..len);
+        assert_eq!(sf.count_active(), len);
+        assert_eq!(sf.active_indices().last(), Some(len - 1));
+        check_consistency(&sf);
+    }
+}
+
+/// Skipping or unskipping a slot twice is the same as doing it once.
+pub fn idempotent_updates<T: Skipfield>() {
+    let mut sf = T::new(130);
+    for i in [0, 5, 63, 64, 129] {
+        sf.skip(i);
+        sf.skip(i);
+    }
+    sf.skip_range(10..20);
+    sf.skip_range(15..25);
+    assert_eq!(sf.count_skipped(), 5 + 15);
+
+    sf.unskip(5);
+    sf.unskip(5);
+    sf.unskip_range(10..12);
+    sf.unskip_range(10..12);
+    assert_eq!(sf.count_skipped(), 4 + 13);
+    assert!(sf.is_active(5) && sf.is_active(11) && sf.is_skipped(12));
+    check_consistency(&sf);
+}
+
+/// Iterators, runs and counts describe the same set of slots.
+pub fn iterator_count_consistency<T: Skipfield>() {
+    for len in LENS {
+        let mut sf = T::new(len);
+        // every third slot, plus a long run that crosses a chunk
+        for i in (0..len).step_by(3) {
+            sf.skip(i);
+        }
+        sf.skip_range(len / 3..len / 2);
+        check_consistency(&sf);
+    }
+}
+
+/// Random operation sequences agree with a `Vec<bool>` model.
+pub fn matches_model<T: Skipfield>() {
+    differential::check::<T>(&Config { max_len: 255, ..Config::default() });
+}
+
+/// Checks that every query agrees with `is_skipped`.
+pub fn check_consistency<T: Skipfield>(sf: &T) {
+    let len = sf.len();
+    let active: Vec<usize> = (0..len).filter(|&i| !sf.is_skipped(i)).collect();
+    let skipped: Vec<usize> = (0..len).filter(|&i| sf.is_skipped(i)).collect();
+
+    assert_eq!(sf.active_indices().collect::<Vec<_>>(), active);
+    assert_eq!(sf.skipped_indices().collect::<Vec<_>>(), skipped);
+    assert_eq!(sf.count_active(), active.len());
+    assert_eq!(sf.count_skipped(), skipped.len());
+    assert_eq!(sf.count_active() + sf.count_skipped(), len);
+    assert_eq!(sf.first_active(), active.first().copied());
+    assert_eq!(sf.first_skipped(), skipped.first().copied());
+    assert_eq!(sf.count_active_in(0..len), active.len());
+    assert_eq!(sf.any_active_in(0..len), !active.is_empty());
+    assert_eq!(sf.is_range_skipped(0..len), active.is_empty());
+
+    for (runs, indices) in [(sf.active_runs().collect::<Vec<_>>(), &active), (sf.skipped_runs().collect(), &skipped)] {
+        let expanded: Vec<usize> = runs.iter().flat_map(|&(start, len)| start..start + len).collect();
+        assert_eq!(&expanded, indices);
+        // maximal: no empty runs and no two runs touching
+        assert!(runs.iter().all(|&(_, len)| len > 0));
+        assert!(runs.windows(2).all(|w| w[0].0 + w[0].1 < w[1].0));
+    }
+}
+
+fn runs_of(ranges: &[Range<usize>]) -> Vec<(usize, usize)> {
+    ranges.iter().filter(|r| !r.is_empty()).map(|r| (r.start, r.len())).collect()
+}
+
+/// Generates the conformance suite as `#[test]` functions for a
+/// [`Skipfield`](crate::Skipfield) implementor.
+///
+/// `skipfield_conformance_tests!(Type)` puts the tests in a `conformance`
+/// module; name the module yourself to cover several types side by side:
+///
+/// ```ignore
+/// skipfield_conformance_tests!(BitmaskSkipfield);
+/// skipfield_conformance_tests!(lcjc_u16: LCJCSkipfield<u16>);
+/// ```
+#[macro_export]
+macro_rules! skipfield_conformance_tests {
+    ($ty:ty) => {
+        $crate::skipfield_conformance_tests!(conformance: $ty);
+    };
+    ($name:ident: $ty:ty) => {
+        mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            $crate::skipfield_conformance_tests!(@tests $ty;
+                new_field_is_all_active,
+                empty_field,
+                fully_skipped_field,
+                chunk_boundaries,
+                tail_bits,
+                idempotent_updates,
+                iterator_count_consistency,
+                matches_model,
+            );
+        }
+    };
+    (@tests $ty:ty; $($check:ident,)*) => {
+        $(
+            #[test]
+            fn $check() {
+                $crate::conformance::$check::<$ty>();
+            }
+        )*
+    };
+}
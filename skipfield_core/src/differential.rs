@@ -0,0 +1,421 @@
+//! Randomized differential testing against a `Vec<bool>` model.
+//!
+//! [`check`] applies random sequences of skips and unskips to a skipfield and
+//! to a plain `Vec<bool>`, comparing every query after each step. A sequence
+//! that diverges (or panics) is shrunk to a minimal reproduction before the
+//! check panics with it, so a failure reads like a hand-written test case.
+//!
+//! Set `SKIPFIELD_SEED` to replay a failure with the seed it reported.
+
+use std::fmt;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::Skipfield;
+
+/// One mutation applied to both the skipfield and the model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    Skip(usize),
+    Unskip(usize),
+    SkipRange(Range<usize>),
+    UnskipRange(Range<usize>),
+}
+
+impl Op {
+    fn apply<T: Skipfield>(&self, sf: &mut T, model: &mut [bool]) {
+        match self {
+            Op::Skip(i) => {
+                sf.skip(*i);
+                model[*i] = true;
+            }
+            Op::Unskip(i) => {
+                sf.unskip(*i);
+                model[*i] = false;
+            }
+            Op::SkipRange(range) => {
+                sf.skip_range(range.clone());
+                model[range.clone()].fill(true);
+            }
+            Op::UnskipRange(range) => {
+                sf.unskip_range(range.clone());
+                model[range.clone()].fill(false);
+            }
+        }
+    }
+
+    // one past the highest index the op touches
+    fn end(&self) -> usize {
+        match self {
+            Op::Skip(i) | Op::Unskip(i) => i + 1,
+            Op::SkipRange(range) | Op::UnskipRange(range) => range.end,
+        }
+    }
+
+    // smaller variants of this op, tried in order while shrinking
+    fn simpler(&self) -> Vec<Op> {
+        let mut out = Vec::new();
+        match self {
+            Op::Skip(i) | Op::Unskip(i) => {
+                if *i > 0 {
+                    out.push(self.with_range(0..1));
+                    out.push(self.with_range(i / 2..i / 2 + 1));
+                    out.push(self.with_range(i - 1..*i));
+                }
+            }
+            Op::SkipRange(range) | Op::UnskipRange(range) => {
+                let Range { start, end } = range.clone();
+                let len = end - start;
+                if len > 0 {
+                    out.push(self.single(start));
+                }
+                if len > 1 {
+                    out.push(self.with_range(start..start + len / 2));
+                    out.push(self.with_range(start + len / 2..end));
+                    out.push(self.with_range(start + 1..end));
+                    out.push(self.with_range(start..end - 1));
+                }
+                if start > 0 {
+                    out.push(self.with_range(0..len));
+                    out.push(self.with_range(start / 2..start / 2 + len));
+                }
+            }
+        }
+        out
+    }
+
+    fn is_skip(&self) -> bool {
+        matches!(self, Op::Skip(_) | Op::SkipRange(_))
+    }
+
+    fn single(&self, i: usize) -> Op {
+        if self.is_skip() {
+            Op::Skip(i)
+        } else {
+            Op::Unskip(i)
+        }
+    }
+
+    fn with_range(&self, range: Range<usize>) -> Op {
+        match self {
+            Op::Skip(_) | Op::Unskip(_) => self.single(range.start),
+            Op::SkipRange(_) => Op::SkipRange(range),
+            Op::UnskipRange(_) => Op::UnskipRange(range),
+        }
+    }
+}
+
+/// How many sequences to run and how large they get.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub seed: u64,
+    pub cases: usize,
+    /// Fields are between 0 and `max_len` slots long.
+    pub max_len: usize,
+    pub max_ops: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self { seed: 0x5eed_f1e1d, cases: 200, max_len: 300, max_ops: 40 }
+    }
+}
+
+/// A sequence the skipfield disagrees with the model on.
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub len: usize,
+    pub ops: Vec<Op>,
+    /// Number of ops applied when the divergence was seen.
+    pub step: usize,
+    pub what: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "field of len {} diverged after {} op(s): {}", self.len, self.step, self.what)?;
+        write!(f, "ops: {:?}", self.ops)
+    }
+}
+
+/// Runs `config.cases` random sequences against `T`, panicking with a shrunk
+/// reproduction on the first divergence.
+pub fn check<T: Skipfield>(config: &Config) {
+    let seed = std::env::var("SKIPFIELD_SEED")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(config.seed);
+    let mut rng = Rng::new(seed);
+    for case in 0..config.cases {
+        let len = rng.below(config.max_len + 1);
+        let ops = random_ops(&mut rng, len, config.max_ops);
+        if let Err(failure) = run::<T>(len, &ops) {
+            let failure = shrink::<T>(failure);
+            panic!(
+                "{} disagrees with the model (seed {seed}, case {case})\n{failure}",
+                std::any::type_name::<T>()
+            );
+        }
+    }
+}
+
+/// Applies `ops` to a new field of `len` slots and to the model, comparing
+/// every query after each step. Panics inside the field count as failures.
+pub fn run<T: Skipfield>(len: usize, ops: &[Op]) -> Result<(), Failure> {
+    let fail = |step, what| Failure { len, ops: ops.to_vec(), step, what };
+    let mut step = 0;
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut sf = T::new(len);
+        let mut model = vec![false; len];
+        compare(&sf, &model, None)?;
+        for op in ops {
+            step += 1;
+            op.apply(&mut sf, &mut model);
+            compare(&sf, &model, Some(op))?;
+        }
+        Ok(())
+    }));
+    match outcome {
+        Ok(result) => result.map_err(|what| fail(step, what)),
+        Err(payload) => {
+            let msg = payload
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| payload.downcast_ref::<&str>().copied())
+                .unwrap_or("non-string payload");
+            Err(fail(step, format!("panicked: {msg}")))
+        }
+    }
+}
+
+/// Shrinks a failing sequence: drops ops, simplifies the ones left and
+/// shortens the field, for as long as it keeps failing.
+pub fn shrink<T: Skipfield>(mut failure: Failure) -> Failure {
+    let still_fails = |len: usize, ops: &[Op]| run::<T>(len, ops).err();
+    // nothing after the failing step matters
+    failure.ops.truncate(failure.step);
+
+    let mut progress = true;
+    while progress {
+        progress = false;
+
+        let mut chunk = failure.ops.len().div_ceil(2).max(1);
+        while chunk > 0 && !failure.ops.is_empty() {
+            let mut start = 0;
+            while start < failure.ops.len() {
+                let mut ops = failure.ops.clone();
+                ops.drain(start..(start + chunk).min(ops.len()));
+                match still_fails(failure.len, &ops) {
+                    Some(smaller) => {
+                        failure = smaller;
+                        progress = true;
+                    }
+                    None => start += chunk,
+                }
+            }
+            chunk /= 2;
+        }
+
+        for i in 0..failure.ops.len() {
+            for simpler in failure.ops[i].simpler() {
+                let mut ops = failure.ops.clone();
+                ops[i] = simpler;
+                if let Some(smaller) = still_fails(failure.len, &ops) {
+                    failure = smaller;
+                    progress = true;
+                    break;
+                }
+            }
+        }
+
+        let needed = failure.ops.iter().map(Op::end).max().unwrap_or(0);
+        for len in [needed, failure.len / 2, failure.len.saturating_sub(1)] {
+            if len >= needed && len < failure.len {
+                if let Some(smaller) = still_fails(len, &failure.ops) {
+                    failure = smaller;
+                    progress = true;
+                    break;
+                }
+            }
+        }
+    }
+    failure
+}
+
+// compares every query, returning a description of the first mismatch
+fn compare<T: Skipfield>(sf: &T, model: &[bool], last: Option<&Op>) -> Result<(), String> {
+    let len = model.len();
+    expect("len()", sf.len(), len)?;
+    expect("is_empty()", sf.is_empty(), len == 0)?;
+    for (i, &skipped) in model.iter().enumerate() {
+        expect(&format!("is_skipped({i})"), sf.is_skipped(i), skipped)?;
+        expect(&format!("is_active({i})"), sf.is_active(i), !skipped)?;
+    }
+
+    let skipped = model.iter().filter(|&&s| s).count();
+    expect("count_skipped()", sf.count_skipped(), skipped)?;
+    expect("count_active()", sf.count_active(), len - skipped)?;
+    expect("first_active()", sf.first_active(), model.iter().position(|&s| !s))?;
+    expect("first_skipped()", sf.first_skipped(), model.iter().position(|&s| s))?;
+
+    let indices = |want: bool| -> Vec<usize> { (0..len).filter(|&i| model[i] == want).collect() };
+    expect("active_indices()", sf.active_indices().collect::<Vec<_>>(), indices(false))?;
+    expect("skipped_indices()", sf.skipped_indices().collect::<Vec<_>>(), indices(true))?;
+    expect("active_runs()", sf.active_runs().collect::<Vec<_>>(), runs(model, false))?;
+    expect("skipped_runs()", sf.skipped_runs().collect::<Vec<_>>(), runs(model, true))?;
+
+    // ranges between a few fixed points and the edges of the last op
+    let mut points = vec![0, len / 4, len / 2, len - len / 4, len];
+    if let Some(op) = last {
+        let end = op.end();
+        points.extend([end.saturating_sub(2), end.saturating_sub(1), end, (end + 1).min(len)]);
+    }
+    points.sort_unstable();
+    points.dedup();
+    for &start in &points {
+        for &end in points.iter().filter(|&&end| end >= start) {
+            let active = model[start..end].iter().filter(|&&s| !s).count();
+            expect(&format!("count_active_in({start}..{end})"), sf.count_active_in(start..end), active)?;
+            expect(&format!("any_active_in({start}..{end})"), sf.any_active_in(start..end), active > 0)?;
+            expect(&format!("is_range_skipped({start}..{end})"), sf.is_range_skipped(start..end), active == 0)?;
+        }
+    }
+    Ok(())
+}
+
+fn expect<V: PartialEq + fmt::Debug>(query: &str, got: V, want: V) -> Result<(), String> {
+    if got == want {
+        Ok(())
+    } else {
+        Err(format!("{query} returned {got:?}, model says {want:?}"))
+    }
+}
+
+fn runs(model: &[bool], skipped: bool) -> Vec<(usize, usize)> {
+    let mut out: Vec<(usize, usize)> = Vec::new();
+    for (i, &s) in model.iter().enumerate() {
+        if s != skipped {
+            continue;
+        }
+        match out.last_mut() {
+            Some((start, len)) if *start + *len == i => *len += 1,
+            _ => out.push((i, 1)),
+        }
+    }
+    out
+}
+
+fn random_ops(rng: &mut Rng, len: usize, max_ops: usize) -> Vec<Op> {
+    if len == 0 {
+        return Vec::new();
+    }
+    let num_ops = rng.below(max_ops + 1);
+    (0..num_ops)
+        .map(|_| {
+            // bias towards word edges, where packed fields tend to go wrong
+            let mut index = || match rng.below(4) {
+                0 => (rng.below(len.div_ceil(64)) * 64 + [0, 1, 63][rng.below(3)]).min(len - 1),
+                1 => len - 1,
+                _ => rng.below(len),
+            };
+            let (a, b) = (index(), index());
+            let range = a.min(b)..a.max(b) + rng.below(2);
+            match rng.below(4) {
+                0 => Op::Skip(a),
+                1 => Op::Unskip(a),
+                2 => Op::SkipRange(range),
+                _ => Op::UnskipRange(range),
+            }
+        })
+        .collect()
+}
+
+/// Small splitmix64 generator, so the harness needs nothing outside std.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform-ish value in `0..n`; `n` must be non-zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a field built straight on a Vec<bool>, optionally with one injected bug
+    struct Flags<const BUGGY: bool>(Vec<bool>);
+
+    impl<const BUGGY: bool> Skipfield for Flags<BUGGY> {
+        fn new(len: usize) -> Self {
+            Self(vec![false; len])
+        }
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn skip(&mut self, idx: usize) {
+            self.0[idx] = true;
+        }
+
+        fn unskip(&mut self, idx: usize) {
+            // forgets to unskip slot 70 once anything past it is skipped
+            self.0[idx] = BUGGY && idx == 70 && self.0[71..].iter().any(|&s| s);
+        }
+
+        fn is_skipped(&self, idx: usize) -> bool {
+            self.0[idx]
+        }
+
+        fn first_active(&self) -> Option<usize> {
+            self.0.iter().position(|&s| !s)
+        }
+
+        fn count_skipped(&self) -> usize {
+            self.0.iter().filter(|&&s| s).count()
+        }
+
+        fn active_indices(&self) -> impl Iterator<Item = usize> + '_ {
+            (0..self.0.len()).filter(|&i| !self.0[i])
+        }
+    }
+
+    crate::skipfield_conformance_tests!(Flags<false>);
+
+    #[test]
+    fn test_failure_shrinks_to_minimal_sequence() {
+        let mut rng = Rng::new(1);
+        let failure = (0..1000)
+            .find_map(|_| {
+                let len = rng.below(300) + 1;
+                run::<Flags<true>>(len, &random_ops(&mut rng, len, 40)).err()
+            })
+            .expect("the injected bug should be found");
+
+        let failure = shrink::<Flags<true>>(failure);
+        assert_eq!(failure.len, 72);
+        assert_eq!(failure.ops, vec![Op::Skip(71), Op::Unskip(70)]);
+        assert_eq!(failure.step, 2);
+    }
+
+    #[test]
+    fn test_panics_are_failures() {
+        let failure = run::<Flags<false>>(10, &[Op::Skip(3), Op::Skip(10)]).unwrap_err();
+        assert_eq!(failure.step, 2);
+        assert!(failure.what.starts_with("panicked"));
+    }
+}
@@ -0,0 +1,103 @@
+use std::ops::Range;
+
+pub mod conformance;
+pub mod differential;
+pub mod simd;
+
+/// Shared interface over every skipfield representation.
+///
+/// A skipfield tracks `len` slots, each of which is either active or skipped.
+/// Indices passed to `skip`, `unskip` and `is_skipped` must be `< len`.
+pub trait Skipfield {
+    /// Longest run of skipped slots a field made by `new` can hold. Skipping
+    /// a longer run panics.
+    const MAX_SKIPPED_RUN: usize = usize::MAX;
+
+    /// Creates a field of `len` slots, all of them active.
+    fn new(len: usize) -> Self
+    where
+        Self: Sized;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn skip(&mut self, idx: usize);
+
+    fn unskip(&mut self, idx: usize);
+
+    fn is_skipped(&self, idx: usize) -> bool;
+
+    fn is_active(&self, idx: usize) -> bool {
+        !self.is_skipped(idx)
+    }
+
+    fn first_active(&self) -> Option<usize>;
+
+    fn first_skipped(&self) -> Option<usize> {
+        self.skipped_indices().next()
+    }
+
+    fn count_skipped(&self) -> usize;
+
+    fn count_active(&self) -> usize {
+        self.len() - self.count_skipped()
+    }
+
+    fn skip_range(&mut self, range: Range<usize>) {
+        for i in range {
+            self.skip(i);
+        }
+    }
+
+    fn unskip_range(&mut self, range: Range<usize>) {
+        for i in range {
+            self.unskip(i);
+        }
+    }
+
+    /// True if every slot in `range` is skipped. Empty ranges count as skipped.
+    fn is_range_skipped(&self, range: Range<usize>) -> bool {
+        !self.any_active_in(range)
+    }
+
+    fn any_active_in(&self, mut range: Range<usize>) -> bool {
+        range.any(|i| !self.is_skipped(i))
+    }
+
+    fn count_active_in(&self, range: Range<usize>) -> usize {
+        range.filter(|&i| !self.is_skipped(i)).count()
+    }
+
+    /// Active indices in ascending order.
+    fn active_indices(&self) -> impl Iterator<Item = usize> + '_;
+
+    /// Skipped indices in ascending order.
+    fn skipped_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len()).filter(move |&i| self.is_skipped(i))
+    }
+
+    /// Maximal runs of active slots as `(start, len)`, in ascending order.
+    fn active_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        group_runs(self.active_indices())
+    }
+
+    /// Maximal runs of skipped slots as `(start, len)`, in ascending order.
+    fn skipped_runs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        group_runs(self.skipped_indices())
+    }
+}
+
+fn group_runs(indices: impl Iterator<Item = usize>) -> impl Iterator<Item = (usize, usize)> {
+    let mut indices = indices.peekable();
+    std::iter::from_fn(move || {
+        let start = indices.next()?;
+        let mut len = 1;
+        while indices.next_if_eq(&(start + len)).is_some() {
+            len += 1;
+        }
+        Some((start, len))
+    })
+}
@@ -0,0 +1,398 @@
+//! Vectorized scans shared by the skipfields.
+//!
+//! Every function picks the widest path the CPU supports at runtime: AVX2,
+//! then SSE2, then a portable scalar loop. All paths return the same results.
+
+/// Number of set bits across `words`.
+pub fn popcount(words: &[u64]) -> usize {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: AVX2 support was just detected
+            return unsafe { x86::popcount_avx2(words) };
+        }
+        if is_x86_feature_detected!("sse2") {
+            // SAFETY: SSE2 support was just detected
+            return unsafe { x86::popcount_sse2(words) };
+        }
+    }
+    portable::popcount(words)
+}
+
+/// Index of the first word that isn't equal to `value`.
+pub fn first_word_not(words: &[u64], value: u64) -> Option<usize> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: AVX2 support was just detected
+            return unsafe { x86::first_word_not_avx2(words, value) };
+        }
+        if is_x86_feature_detected!("sse2") {
+            // SAFETY: SSE2 support was just detected
+            return unsafe { x86::first_word_not_sse2(words, value) };
+        }
+    }
+    portable::first_word_not(words, value)
+}
+
+/// Number of `true` flags.
+pub fn count_true(flags: &[bool]) -> usize {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: AVX2 support was just detected
+            return unsafe { x86::count_true_avx2(flags) };
+        }
+        if is_x86_feature_detected!("sse2") {
+            // SAFETY: SSE2 support was just detected
+            return unsafe { x86::count_true_sse2(flags) };
+        }
+    }
+    portable::count_true(flags)
+}
+
+/// Index of the first `false` flag.
+pub fn first_false(flags: &[bool]) -> Option<usize> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: AVX2 support was just detected
+            return unsafe { x86::first_false_avx2(flags) };
+        }
+        if is_x86_feature_detected!("sse2") {
+            // SAFETY: SSE2 support was just detected
+            return unsafe { x86::first_false_sse2(flags) };
+        }
+    }
+    portable::first_false(flags)
+}
+
+/// Packs flags into words, 64 per word: flag `i` becomes bit `i % 64` of word
+/// `i / 64`. Bits past the last flag are zero.
+pub fn pack_bools(flags: &[bool]) -> Vec<u64> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: AVX2 support was just detected
+            return unsafe { x86::pack_bools_avx2(flags) };
+        }
+        if is_x86_feature_detected!("sse2") {
+            // SAFETY: SSE2 support was just detected
+            return unsafe { x86::pack_bools_sse2(flags) };
+        }
+    }
+    portable::pack_bools(flags)
+}
+
+mod portable {
+    pub(super) fn popcount(words: &[u64]) -> usize {
+        words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub(super) fn first_word_not(words: &[u64], value: u64) -> Option<usize> {
+        words.iter().position(|&w| w != value)
+    }
+
+    pub(super) fn count_true(flags: &[bool]) -> usize {
+        flags.iter().filter(|&&flag| flag).count()
+    }
+
+    pub(super) fn first_false(flags: &[bool]) -> Option<usize> {
+        flags.iter().position(|&flag| !flag)
+    }
+
+    pub(super) fn pack_bools(flags: &[bool]) -> Vec<u64> {
+        flags
+            .chunks(64)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0u64, |word, (bit, &flag)| word | ((flag as u64) << bit))
+            })
+            .collect()
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    use super::portable;
+
+    // per-nibble popcounts, repeated for both 128-bit lanes of `pshufb`
+    const NIBBLE_COUNTS: [i8; 16] = [0, 1, 1, 2, 1, 2, 2, 3, 1, 2, 2, 3, 2, 3, 3, 4];
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn popcount_avx2(words: &[u64]) -> usize {
+        let n = NIBBLE_COUNTS;
+        let lookup = _mm256_setr_epi8(
+            n[0], n[1], n[2], n[3], n[4], n[5], n[6], n[7], n[8], n[9], n[10], n[11], n[12], n[13], n[14], n[15],
+            n[0], n[1], n[2], n[3], n[4], n[5], n[6], n[7], n[8], n[9], n[10], n[11], n[12], n[13], n[14], n[15],
+        );
+        let low_nibbles = _mm256_set1_epi8(0x0f);
+        let zero = _mm256_setzero_si256();
+
+        let blocks = words.chunks_exact(4);
+        let rest = blocks.remainder();
+        let mut acc = zero;
+        for block in blocks {
+            let v = _mm256_loadu_si256(block.as_ptr() as *const __m256i);
+            let lo = _mm256_and_si256(v, low_nibbles);
+            let hi = _mm256_and_si256(_mm256_srli_epi16(v, 4), low_nibbles);
+            let bytes = _mm256_add_epi8(_mm256_shuffle_epi8(lookup, lo), _mm256_shuffle_epi8(lookup, hi));
+            acc = _mm256_add_epi64(acc, _mm256_sad_epu8(bytes, zero));
+        }
+
+        let mut lanes = [0u64; 4];
+        _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, acc);
+        lanes.iter().sum::<u64>() as usize + portable::popcount(rest)
+    }
+
+    // SSE2 has no byte shuffle, so this counts bits with the usual SWAR steps
+    // on two words at a time
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn popcount_sse2(words: &[u64]) -> usize {
+        let m1 = _mm_set1_epi8(0x55);
+        let m2 = _mm_set1_epi8(0x33);
+        let m4 = _mm_set1_epi8(0x0f);
+        let zero = _mm_setzero_si128();
+
+        let blocks = words.chunks_exact(2);
+        let rest = blocks.remainder();
+        let mut acc = zero;
+        for block in blocks {
+            let mut v = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+            v = _mm_sub_epi8(v, _mm_and_si128(_mm_srli_epi64(v, 1), m1));
+            v = _mm_add_epi8(_mm_and_si128(v, m2), _mm_and_si128(_mm_srli_epi64(v, 2), m2));
+            v = _mm_and_si128(_mm_add_epi8(v, _mm_srli_epi64(v, 4)), m4);
+            acc = _mm_add_epi64(acc, _mm_sad_epu8(v, zero));
+        }
+
+        let mut lanes = [0u64; 2];
+        _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, acc);
+        (lanes[0] + lanes[1]) as usize + portable::popcount(rest)
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn first_word_not_avx2(words: &[u64], value: u64) -> Option<usize> {
+        let needle = _mm256_set1_epi64x(value as i64);
+        let blocks = words.chunks_exact(4);
+        let rest = blocks.remainder();
+        for (block_i, block) in blocks.enumerate() {
+            let v = _mm256_loadu_si256(block.as_ptr() as *const __m256i);
+            let equal = _mm256_movemask_epi8(_mm256_cmpeq_epi64(v, needle)) as u32;
+            if equal != u32::MAX {
+                return Some(block_i * 4 + (!equal).trailing_zeros() as usize / 8);
+            }
+        }
+        let done = words.len() - rest.len();
+        portable::first_word_not(rest, value).map(|i| done + i)
+    }
+
+    // SSE2 only compares 32-bit lanes, so a mismatch is narrowed down to its
+    // word by looking at which bytes differ
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn first_word_not_sse2(words: &[u64], value: u64) -> Option<usize> {
+        let needle = _mm_set1_epi64x(value as i64);
+        let blocks = words.chunks_exact(2);
+        let rest = blocks.remainder();
+        for (block_i, block) in blocks.enumerate() {
+            let v = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+            let equal = _mm_movemask_epi8(_mm_cmpeq_epi32(v, needle)) as u32;
+            if equal != 0xffff {
+                return Some(block_i * 2 + (!equal).trailing_zeros() as usize / 8);
+            }
+        }
+        let done = words.len() - rest.len();
+        portable::first_word_not(rest, value).map(|i| done + i)
+    }
+
+    // `bool` is a byte holding 0 or 1, so flags can be loaded as bytes
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn count_true_avx2(flags: &[bool]) -> usize {
+        let zero = _mm256_setzero_si256();
+        let blocks = flags.chunks_exact(32);
+        let rest = blocks.remainder();
+        let mut acc = zero;
+        for block in blocks {
+            let v = _mm256_loadu_si256(block.as_ptr() as *const __m256i);
+            acc = _mm256_add_epi64(acc, _mm256_sad_epu8(v, zero));
+        }
+
+        let mut lanes = [0u64; 4];
+        _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, acc);
+        lanes.iter().sum::<u64>() as usize + portable::count_true(rest)
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn count_true_sse2(flags: &[bool]) -> usize {
+        let zero = _mm_setzero_si128();
+        let blocks = flags.chunks_exact(16);
+        let rest = blocks.remainder();
+        let mut acc = zero;
+        for block in blocks {
+            let v = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+            acc = _mm_add_epi64(acc, _mm_sad_epu8(v, zero));
+        }
+
+        let mut lanes = [0u64; 2];
+        _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, acc);
+        (lanes[0] + lanes[1]) as usize + portable::count_true(rest)
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn first_false_avx2(flags: &[bool]) -> Option<usize> {
+        let zero = _mm256_setzero_si256();
+        let blocks = flags.chunks_exact(32);
+        let rest = blocks.remainder();
+        for (block_i, block) in blocks.enumerate() {
+            let v = _mm256_loadu_si256(block.as_ptr() as *const __m256i);
+            let falses = _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, zero)) as u32;
+            if falses != 0 {
+                return Some(block_i * 32 + falses.trailing_zeros() as usize);
+            }
+        }
+        let done = flags.len() - rest.len();
+        portable::first_false(rest).map(|i| done + i)
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn first_false_sse2(flags: &[bool]) -> Option<usize> {
+        let zero = _mm_setzero_si128();
+        let blocks = flags.chunks_exact(16);
+        let rest = blocks.remainder();
+        for (block_i, block) in blocks.enumerate() {
+            let v = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+            let falses = _mm_movemask_epi8(_mm_cmpeq_epi8(v, zero)) as u32;
+            if falses != 0 {
+                return Some(block_i * 16 + falses.trailing_zeros() as usize);
+            }
+        }
+        let done = flags.len() - rest.len();
+        portable::first_false(rest).map(|i| done + i)
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn pack_bools_avx2(flags: &[bool]) -> Vec<u64> {
+        let zero = _mm256_setzero_si256();
+        let blocks = flags.chunks_exact(64);
+        let rest = blocks.remainder();
+        let mut words = Vec::with_capacity(flags.len().div_ceil(64));
+        for block in blocks {
+            let lo = _mm256_loadu_si256(block.as_ptr() as *const __m256i);
+            let hi = _mm256_loadu_si256(block.as_ptr().add(32) as *const __m256i);
+            let lo_bits = !(_mm256_movemask_epi8(_mm256_cmpeq_epi8(lo, zero)) as u32);
+            let hi_bits = !(_mm256_movemask_epi8(_mm256_cmpeq_epi8(hi, zero)) as u32);
+            words.push(lo_bits as u64 | (hi_bits as u64) << 32);
+        }
+        words.extend(portable::pack_bools(rest));
+        words
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn pack_bools_sse2(flags: &[bool]) -> Vec<u64> {
+        let zero = _mm_setzero_si128();
+        let blocks = flags.chunks_exact(64);
+        let rest = blocks.remainder();
+        let mut words = Vec::with_capacity(flags.len().div_ceil(64));
+        for block in blocks {
+            let mut word = 0u64;
+            for quarter in 0..4 {
+                let v = _mm_loadu_si128(block.as_ptr().add(quarter * 16) as *const __m128i);
+                let bits = !_mm_movemask_epi8(_mm_cmpeq_epi8(v, zero)) as u64 & 0xffff;
+                word |= bits << (quarter * 16);
+            }
+            words.push(word);
+        }
+        words.extend(portable::pack_bools(rest));
+        words
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // deterministic xorshift, so the tests need no rng crate
+    fn words(n: usize, seed: u64) -> Vec<u64> {
+        let mut x = seed;
+        (0..n)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x
+            })
+            .collect()
+    }
+
+    fn flags(n: usize, seed: u64) -> Vec<bool> {
+        words(n, seed).into_iter().map(|w| w % 3 == 0).collect()
+    }
+
+    #[test]
+    fn test_word_scans_match_portable() {
+        for n in [0, 1, 2, 3, 4, 5, 7, 8, 63, 100] {
+            let mut ws = words(n, 0x9e37_79b9_7f4a_7c15);
+            assert_eq!(popcount(&ws), portable::popcount(&ws));
+
+            ws.iter_mut().for_each(|w| *w = u64::MAX);
+            assert_eq!(first_word_not(&ws, u64::MAX), None);
+            for i in 0..n {
+                // a mismatch in the top half only, which 32-bit compares must not miss
+                ws[i] = u64::MAX >> 1;
+                assert_eq!(first_word_not(&ws, u64::MAX), Some(i));
+                ws[i] = u64::MAX;
+            }
+        }
+    }
+
+    #[test]
+    fn test_flag_scans_match_portable() {
+        for n in [0, 1, 15, 16, 17, 31, 32, 33, 64, 65, 200, 1000] {
+            let fs = flags(n, 0x1234_5678_9abc_def1);
+            assert_eq!(count_true(&fs), portable::count_true(&fs));
+            assert_eq!(pack_bools(&fs), portable::pack_bools(&fs));
+
+            let mut all = vec![true; n];
+            assert_eq!(first_false(&all), None);
+            for i in (0..n).step_by(7) {
+                all[i] = false;
+                assert_eq!(first_false(&all), Some(i));
+                all[i] = true;
+            }
+        }
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[test]
+    fn test_each_x86_path() {
+        let ws = words(1001, 42);
+        let fs = flags(1001, 42);
+        if is_x86_feature_detected!("sse2") {
+            // SAFETY: SSE2 support was just detected
+            unsafe {
+                assert_eq!(x86::popcount_sse2(&ws), portable::popcount(&ws));
+                assert_eq!(x86::first_word_not_sse2(&ws, ws[0]), Some(1));
+                assert_eq!(x86::count_true_sse2(&fs), portable::count_true(&fs));
+                assert_eq!(x86::first_false_sse2(&fs), portable::first_false(&fs));
+                assert_eq!(x86::pack_bools_sse2(&fs), portable::pack_bools(&fs));
+            }
+        }
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: AVX2 support was just detected
+            unsafe {
+                assert_eq!(x86::popcount_avx2(&ws), portable::popcount(&ws));
+                assert_eq!(x86::first_word_not_avx2(&ws, ws[0]), Some(1));
+                assert_eq!(x86::count_true_avx2(&fs), portable::count_true(&fs));
+                assert_eq!(x86::first_false_avx2(&fs), portable::first_false(&fs));
+                assert_eq!(x86::pack_bools_avx2(&fs), portable::pack_bools(&fs));
+            }
+        }
+    }
+}